@@ -0,0 +1,518 @@
+//! Per subreddit rollups by day and by month: comments, posts, distinct authors, mean and median
+//! scores, in `subreddit_activity`, and the most active authors, in `subreddit_top_author`.
+//!
+//! Medians and distinct authors can't be merged from earlier totals, so instead of adding to the
+//! rollups, each update recomputes every period that gained comments or posts since the last one.
+//! `aggregate_progress` keeps the highest comment and posts id already rolled up.
+
+use anyhow::Result;
+use hashbrown::{HashMap, HashSet};
+
+use crate::{database::Database, flair_history::DELETED, timestamp, value::Value};
+
+const DAY: i64 = 86_400;
+
+/// Authors kept per subreddit and period when no other number is asked for.
+pub const TOP_AUTHORS: usize = 10;
+
+// The tables rolled up, in the order their rows are tagged with in ROWS.
+const SOURCES: [&str; 2] = ["comment", "posts"];
+
+// New rows of a source, as the days and subreddits they fall in.
+const CHANGED: &str = "SELECT DISTINCT subreddit, created_utc / 86400 FROM {table} \
+                       WHERE id > $1 AND id <= $2 AND subreddit IS NOT NULL AND created_utc IS NOT NULL";
+
+// Comments and posts in a time range, oldest first, so each month is complete once a later one
+// starts.
+const ROWS: &str = "
+SELECT 0, subreddit, author, score, created_utc FROM comment WHERE created_utc >= $1 AND created_utc < $2
+UNION ALL
+SELECT 1, subreddit, author, score, created_utc FROM posts WHERE created_utc >= $1 AND created_utc < $2
+ORDER BY 5";
+
+const INSERT_ACTIVITY: &str = "INSERT INTO subreddit_activity \
+    (period, subreddit, start, comments, posts, authors, mean_comment_score, median_comment_score, mean_post_score, median_post_score) \
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)";
+
+const INSERT_TOP_AUTHOR: &str = "INSERT INTO subreddit_top_author \
+    (period, subreddit, start, rank, author, comments, posts) VALUES ($1, $2, $3, $4, $5, $6, $7)";
+
+const SAVE_PROGRESS: &str = "INSERT INTO aggregate_progress (source, last_id) VALUES ($1, $2) \
+    ON CONFLICT (source) DO UPDATE SET last_id = excluded.last_id";
+
+/// What an update did.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Comments and posts added since the last update.
+    pub new_rows: i64,
+    /// Days and months recomputed.
+    pub periods: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Period {
+    Day,
+    Month,
+}
+
+impl Period {
+    fn name(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Month => "month",
+        }
+    }
+}
+
+/// Everything counted for one subreddit in one period.
+#[derive(Default)]
+struct Bucket {
+    comment_scores: Vec<i64>,
+    post_scores: Vec<i64>,
+    comments: i64,
+    posts: i64,
+    /// Comments and posts per author, without deleted ones.
+    authors: HashMap<String, (i64, i64)>,
+}
+
+impl Bucket {
+    fn add(&mut self, is_post: bool, author: Option<String>, score: Option<i64>) {
+        let (count, scores) = if is_post {
+            (&mut self.posts, &mut self.post_scores)
+        } else {
+            (&mut self.comments, &mut self.comment_scores)
+        };
+        *count += 1;
+        scores.extend(score);
+        if let Some(author) = author.filter(|author| author != DELETED) {
+            let counts = self.authors.entry(author).or_default();
+            if is_post {
+                counts.1 += 1;
+            } else {
+                counts.0 += 1;
+            }
+        }
+    }
+}
+
+/// Rows to write for finished buckets.
+#[derive(Default)]
+struct Rollup {
+    activity: Vec<Vec<Value>>,
+    top_authors: Vec<Vec<Value>>,
+}
+
+impl Rollup {
+    fn push(&mut self, period: Period, subreddit: &str, start: i64, bucket: Bucket, top: usize) {
+        let key = |values: &mut Vec<Value>| {
+            values.push(Value::Text(period.name().to_string()));
+            values.push(Value::Text(subreddit.to_string()));
+            values.push(Value::Integer(start));
+        };
+        let mut row = Vec::new();
+        key(&mut row);
+        row.push(Value::Integer(bucket.comments));
+        row.push(Value::Integer(bucket.posts));
+        row.push(Value::Integer(bucket.authors.len() as i64));
+        for mut scores in [bucket.comment_scores, bucket.post_scores] {
+            row.push(mean(&scores));
+            row.push(median(&mut scores));
+        }
+        self.activity.push(row);
+
+        let mut authors: Vec<(String, (i64, i64))> = bucket.authors.into_iter().collect();
+        authors.sort_unstable_by(|(a, (a_comments, a_posts)), (b, (b_comments, b_posts))| {
+            (b_comments + b_posts)
+                .cmp(&(a_comments + a_posts))
+                .then_with(|| a.cmp(b))
+        });
+        for (rank, (author, (comments, posts))) in authors.into_iter().take(top).enumerate() {
+            let mut row = Vec::new();
+            key(&mut row);
+            row.push(Value::Integer(rank as i64 + 1));
+            row.push(Value::Text(author));
+            row.push(Value::Integer(comments));
+            row.push(Value::Integer(posts));
+            self.top_authors.push(row);
+        }
+    }
+}
+
+fn mean(scores: &[i64]) -> Value {
+    if scores.is_empty() {
+        return Value::Null;
+    }
+    Value::Real(scores.iter().sum::<i64>() as f64 / scores.len() as f64)
+}
+
+fn median(scores: &mut [i64]) -> Value {
+    if scores.is_empty() {
+        return Value::Null;
+    }
+    scores.sort_unstable();
+    let middle = scores.len() / 2;
+    if scores.len() % 2 == 0 {
+        Value::Real((scores[middle - 1] + scores[middle]) as f64 / 2.0)
+    } else {
+        Value::Real(scores[middle] as f64)
+    }
+}
+
+/// Brings the rollups up to date with the comment and posts tables, keeping `top_authors`
+/// authors per period. With `rebuild` every period is recomputed rather than only the ones with
+/// new rows.
+pub fn update(db: &mut Database, rebuild: bool, top_authors: usize) -> Result<Summary> {
+    let mut summary = Summary::default();
+    let mut progress = HashMap::new();
+    if !rebuild {
+        db.query_each(
+            "SELECT source, last_id FROM aggregate_progress",
+            &[],
+            |row| {
+                if let (Value::Text(source), Some(last_id)) = (&row[0], row[1].as_i64()) {
+                    progress.insert(source.clone(), last_id);
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    // Days that gained rows, per subreddit, and the months they are in.
+    let mut changed: HashMap<String, HashSet<(Period, i64)>> = HashMap::new();
+    let mut saved = Vec::new();
+    for source in &SOURCES {
+        let last_id = progress.get(*source).copied().unwrap_or(0);
+        let mut max_id = last_id;
+        db.query_each(&format!("SELECT max(id) FROM {}", source), &[], |row| {
+            max_id = row[0].as_i64().unwrap_or(0).max(last_id);
+            Ok(())
+        })?;
+        let params = [Value::Integer(last_id), Value::Integer(max_id)];
+        db.query_each(&CHANGED.replace("{table}", source), &params, |row| {
+            if let (Value::Text(subreddit), Some(day)) = (&row[0], row[1].as_i64()) {
+                let periods = changed.entry(subreddit.clone()).or_default();
+                periods.insert((Period::Day, day * DAY));
+                periods.insert((Period::Month, timestamp::month_start(day * DAY)));
+            }
+            Ok(())
+        })?;
+        db.query_each(
+            &format!("SELECT count(*) FROM {} WHERE id > $1 AND id <= $2", source),
+            &params,
+            |row| {
+                summary.new_rows += row[0].as_i64().unwrap_or(0);
+                Ok(())
+            },
+        )?;
+        saved.push(vec![
+            Value::Text(source.to_string()),
+            Value::Integer(max_id),
+        ]);
+    }
+    summary.periods = changed.values().map(HashSet::len).sum();
+
+    let mut rollup = Rollup::default();
+    let months = changed
+        .values()
+        .flatten()
+        .filter(|(period, _)| *period == Period::Month)
+        .map(|(_, start)| *start);
+    if let (Some(first), Some(last)) = (months.clone().min(), months.max()) {
+        // Buckets of the month being read. Rows come oldest first, so a month's buckets are done
+        // once a row from a later month turns up.
+        let mut month = first;
+        let mut buckets: HashMap<(String, Period, i64), Bucket> = HashMap::new();
+        let params = [
+            Value::Integer(first),
+            Value::Integer(timestamp::next_month(last)),
+        ];
+        db.query_each(ROWS, &params, |row| {
+            let mut row = row.into_iter();
+            let is_post = row.next().unwrap().as_i64() == Some(1);
+            // Null subreddits skip the row and Null authors aren't counted.
+            let mut text = || {
+                row.next()
+                    .filter(|value| *value != Value::Null)
+                    .map(|value| value.to_text())
+            };
+            let subreddit = text();
+            let author = text();
+            let score = row.next().and_then(|value| value.as_i64());
+            let created_utc = row.next().and_then(|value| value.as_i64());
+            let (subreddit, created_utc) = match (subreddit, created_utc) {
+                (Some(subreddit), Some(created_utc)) => (subreddit, created_utc),
+                _ => return Ok(()),
+            };
+            if timestamp::month_start(created_utc) != month {
+                for ((subreddit, period, start), bucket) in buckets.drain() {
+                    rollup.push(period, &subreddit, start, bucket, top_authors);
+                }
+                month = timestamp::month_start(created_utc);
+            }
+            let periods = match changed.get(&subreddit) {
+                Some(periods) => periods,
+                None => return Ok(()),
+            };
+            let day = created_utc.div_euclid(DAY) * DAY;
+            for &key in &[(Period::Day, day), (Period::Month, month)] {
+                if periods.contains(&key) {
+                    buckets
+                        .entry((subreddit.clone(), key.0, key.1))
+                        .or_default()
+                        .add(is_post, author.clone(), score);
+                }
+            }
+            Ok(())
+        })?;
+        for ((subreddit, period, start), bucket) in buckets {
+            rollup.push(period, &subreddit, start, bucket, top_authors);
+        }
+    }
+
+    // Old rows of the recomputed periods go in the same transaction as the new ones.
+    let mut statements = Vec::new();
+    if rebuild {
+        statements.push(("DELETE FROM subreddit_activity", vec![vec![]]));
+        statements.push(("DELETE FROM subreddit_top_author", vec![vec![]]));
+    } else {
+        let keys: Vec<Vec<Value>> = changed
+            .iter()
+            .flat_map(|(subreddit, periods)| {
+                periods.iter().map(move |(period, start)| {
+                    vec![
+                        Value::Text(period.name().to_string()),
+                        Value::Text(subreddit.clone()),
+                        Value::Integer(*start),
+                    ]
+                })
+            })
+            .collect();
+        statements.push((
+            "DELETE FROM subreddit_activity WHERE period = $1 AND subreddit = $2 AND start = $3",
+            keys.clone(),
+        ));
+        statements.push((
+            "DELETE FROM subreddit_top_author WHERE period = $1 AND subreddit = $2 AND start = $3",
+            keys,
+        ));
+    }
+    statements.push((INSERT_ACTIVITY, rollup.activity));
+    statements.push((INSERT_TOP_AUTHOR, rollup.top_authors));
+    statements.push((SAVE_PROGRESS, saved));
+    db.execute_batch(&statements)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{comment::Comment, test_support};
+
+    const JUNE_30: i64 = 1561852800;
+    const JULY_1: i64 = 1561939200;
+
+    fn comment(id: &str, author: &str, score: i64, created_utc: i64) -> Comment {
+        test_support::comment(id, author, "test", score, created_utc)
+    }
+
+    fn rows(db: &mut Database, sql: &str) -> Vec<Vec<Value>> {
+        let mut rows = Vec::new();
+        db.query_each(sql, &[], |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .unwrap();
+        rows
+    }
+
+    // Period, start, comments, posts, authors and the comment score mean and median.
+    fn activity(db: &mut Database) -> Vec<(String, i64, i64, i64, i64, Value, Value)> {
+        rows(
+            db,
+            "SELECT period, start, comments, posts, authors, mean_comment_score, median_comment_score \
+             FROM subreddit_activity ORDER BY period, start",
+        )
+        .into_iter()
+        .map(|row| {
+            let mut row = row.into_iter();
+            let mut next = || row.next().unwrap();
+            (
+                next().to_text(),
+                next().as_i64().unwrap(),
+                next().as_i64().unwrap(),
+                next().as_i64().unwrap(),
+                next().as_i64().unwrap(),
+                next(),
+                next(),
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn means_and_medians() {
+        assert_eq!(mean(&[]), Value::Null);
+        assert_eq!(median(&mut []), Value::Null);
+        assert_eq!(mean(&[1, 2, 6]), Value::Real(3.0));
+        assert_eq!(median(&mut [6, 1, 2]), Value::Real(2.0));
+        assert_eq!(median(&mut [4, 1, 3, 10]), Value::Real(3.5));
+    }
+
+    #[test]
+    fn top_authors_leave_out_deleted() {
+        let mut bucket = Bucket::default();
+        for (is_post, author) in &[
+            (false, "bob"),
+            (true, "alice"),
+            (false, "alice"),
+            (false, DELETED),
+            (false, DELETED),
+            (false, DELETED),
+            (false, "carol"),
+        ] {
+            bucket.add(*is_post, Some(author.to_string()), Some(1));
+        }
+        let mut rollup = Rollup::default();
+        rollup.push(Period::Day, "test", 0, bucket, 2);
+        assert_eq!(
+            rollup.activity[0][3..6],
+            [Value::Integer(6), Value::Integer(1), Value::Integer(3)]
+        );
+        let top: Vec<_> = rollup
+            .top_authors
+            .iter()
+            .map(|row| (row[3].clone(), row[4].clone()))
+            .collect();
+        assert_eq!(
+            top,
+            [
+                (Value::Integer(1), Value::Text("alice".to_string())),
+                (Value::Integer(2), Value::Text("bob".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_only_changed_periods() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        for comment in &[
+            comment("a", "alice", 1, JUNE_30),
+            comment("b", "bob", 4, JUNE_30 + 60),
+            comment("c", "alice", 10, JULY_1 + 60),
+        ] {
+            db.insert_comment(comment).unwrap();
+        }
+        db.insert_post(&test_support::post("post", "carol", "test", 1, JULY_1))
+            .unwrap();
+        db.commit().unwrap();
+
+        let summary = update(&mut db, false, TOP_AUTHORS).unwrap();
+        assert_eq!((summary.new_rows, summary.periods), (4, 4));
+        let june = (
+            "day".to_string(),
+            JUNE_30,
+            2,
+            0,
+            2,
+            Value::Real(2.5),
+            Value::Real(2.5),
+        );
+        assert_eq!(
+            activity(&mut db),
+            [
+                june.clone(),
+                (
+                    "day".to_string(),
+                    JULY_1,
+                    1,
+                    1,
+                    2,
+                    Value::Real(10.0),
+                    Value::Real(10.0)
+                ),
+                (
+                    "month".to_string(),
+                    JUNE_30 - 29 * DAY,
+                    2,
+                    0,
+                    2,
+                    Value::Real(2.5),
+                    Value::Real(2.5)
+                ),
+                (
+                    "month".to_string(),
+                    JULY_1,
+                    1,
+                    1,
+                    2,
+                    Value::Real(10.0),
+                    Value::Real(10.0)
+                ),
+            ]
+        );
+
+        // Nothing new, nothing recomputed.
+        let summary = update(&mut db, false, TOP_AUTHORS).unwrap();
+        assert_eq!((summary.new_rows, summary.periods), (0, 0));
+
+        // A later July comment only recomputes July.
+        db.insert_comment(&comment("d", "[deleted]", 3, JULY_1 + 120))
+            .unwrap();
+        db.commit().unwrap();
+        let summary = update(&mut db, false, TOP_AUTHORS).unwrap();
+        assert_eq!((summary.new_rows, summary.periods), (1, 2));
+        let july = activity(&mut db);
+        assert_eq!(july[0], june);
+        assert_eq!(
+            july[1],
+            (
+                "day".to_string(),
+                JULY_1,
+                2,
+                1,
+                2,
+                Value::Real(6.5),
+                Value::Real(6.5)
+            )
+        );
+        assert_eq!(
+            rows(
+                &mut db,
+                "SELECT author, comments, posts FROM subreddit_top_author \
+                 WHERE period = 'month' AND start = 1561939200 ORDER BY rank"
+            ),
+            [
+                vec![
+                    Value::Text("alice".to_string()),
+                    Value::Integer(1),
+                    Value::Integer(0)
+                ],
+                vec![
+                    Value::Text("carol".to_string()),
+                    Value::Integer(0),
+                    Value::Integer(1)
+                ],
+            ]
+        );
+
+        update(&mut db, true, TOP_AUTHORS).unwrap();
+        assert_eq!(activity(&mut db), july);
+    }
+
+    #[test]
+    fn new_rows_leave_out_deleted_ids() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        for id in &["a", "b", "c"] {
+            db.insert_comment(&comment(id, "alice", 1, JUNE_30))
+                .unwrap();
+        }
+        db.commit().unwrap();
+        db.execute("DELETE FROM comment WHERE reddit_id = 'b'", &[])
+            .unwrap();
+
+        let summary = update(&mut db, false, TOP_AUTHORS).unwrap();
+        assert_eq!((summary.new_rows, summary.periods), (2, 2));
+    }
+}
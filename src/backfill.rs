@@ -0,0 +1,166 @@
+//! Updating columns of rows that are already in the database from the dump, matched by reddit id.
+//!
+//! Rows are staged in a temporary table shaped like the target columns and applied with one
+//! `UPDATE ... FROM` per batch. `update_flair` is the same thing with the flair columns derived
+//! through the flair mapping.
+
+use anyhow::{bail, Result};
+
+use crate::value::Value;
+
+/// Which columns of which table to update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backfill {
+    pub table: &'static str,
+    /// Column holding the reddit id, `reddit_id` for comments and `pid` for posts.
+    pub key: &'static str,
+    /// Columns to set. They are named after the dump fields they are read from.
+    pub columns: Vec<String>,
+    /// Leave the column alone where the dump has no value, instead of setting it to NULL.
+    pub keep_existing: bool,
+}
+
+impl Backfill {
+    /// Backfill of dump `fields` into `table`, which must be `comment` or `posts`.
+    pub fn from_fields<'a>(table: &str, fields: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let (table, key) = match table {
+            "comment" => ("comment", "reddit_id"),
+            "posts" => ("posts", "pid"),
+            _ => bail!("Can't backfill table {}", table),
+        };
+        let mut columns: Vec<String> = Vec::new();
+        for field in fields {
+            if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                bail!("Invalid field name {:?}", field);
+            }
+            if field == "id" || field == key {
+                bail!(
+                    "{} is used to find the rows to update, it can't be backfilled",
+                    field
+                );
+            }
+            if !columns.iter().any(|column| column == field) {
+                columns.push(field.to_string());
+            }
+        }
+        if columns.is_empty() {
+            bail!("No fields given to backfill, use --fields");
+        }
+        Ok(Backfill {
+            table,
+            key,
+            columns,
+            keep_existing: true,
+        })
+    }
+
+    /// The flair columns written by `update_flair`.
+    pub fn flair() -> Self {
+        Backfill {
+            table: "comment",
+            key: "reddit_id",
+            columns: vec![
+                "flair".to_string(),
+                "flair_id".to_string(),
+                "author_flair_text".to_string(),
+            ],
+            keep_existing: false,
+        }
+    }
+
+    /// Reads the reddit id and the backfilled fields from a line of the dump. Returns None for
+    /// lines without an id.
+    pub fn row(&self, json: &serde_json::Value) -> Option<(String, Vec<Value>)> {
+        let id = json.get("id")?.as_str()?.to_string();
+        let values = self
+            .columns
+            .iter()
+            .map(|column| json.get(column).map_or(Value::Null, Value::from_json))
+            .collect();
+        Some((id, values))
+    }
+
+    /// `SET` clause applying the staging table `s` to the target table.
+    pub fn assignments(&self) -> String {
+        self.columns
+            .iter()
+            .map(|column| {
+                if self.keep_existing {
+                    format!("{0} = coalesce(s.{0}, {1}.{0})", column, self.table)
+                } else {
+                    format!("{0} = s.{0}", column)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Outcome of applying staged updates: rows that were found and updated, and updates whose
+/// reddit id is not in the table.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateCounts {
+    pub matched: usize,
+    pub missing: usize,
+}
+
+impl UpdateCounts {
+    pub fn add(&mut self, other: &UpdateCounts) {
+        self.matched += other.matched;
+        self.missing += other.missing;
+    }
+
+    pub fn print(&self, what: &str) {
+        println!(
+            "{}: {} rows updated, {} not found",
+            what, self.matched, self.missing
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let backfill = Backfill::from_fields("posts", "score,score,edited".split(',')).unwrap();
+        assert_eq!((backfill.table, backfill.key), ("posts", "pid"));
+        assert_eq!(backfill.columns, ["score", "edited"]);
+        for (table, fields) in &[
+            ("users", "score"),
+            ("comment", "id"),
+            ("posts", "pid"),
+            ("comment", "score;DROP"),
+            ("comment", ""),
+        ] {
+            assert!(Backfill::from_fields(table, fields.split(',')).is_err());
+        }
+        assert!(Backfill::from_fields("comment", std::iter::empty()).is_err());
+    }
+
+    #[test]
+    fn rows_and_assignments() {
+        let backfill = Backfill::from_fields("comment", "score,edited".split(',')).unwrap();
+        assert_eq!(
+            backfill.row(&serde_json::json!({"id": "abc", "score": 5, "edited": false})),
+            Some((
+                "abc".to_string(),
+                vec![Value::Integer(5), Value::Integer(0)]
+            ))
+        );
+        assert_eq!(
+            backfill.row(&serde_json::json!({"id": "abc"})).unwrap().1,
+            [Value::Null, Value::Null]
+        );
+        assert_eq!(backfill.row(&serde_json::json!({"score": 5})), None);
+        assert_eq!(
+            backfill.assignments(),
+            "score = coalesce(s.score, comment.score), edited = coalesce(s.edited, comment.edited)"
+        );
+        assert_eq!(
+            Backfill::flair().assignments(),
+            "flair = s.flair, flair_id = s.flair_id, author_flair_text = s.author_flair_text"
+        );
+    }
+}
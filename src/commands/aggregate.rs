@@ -0,0 +1,42 @@
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use super::export;
+use crate::aggregate;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("aggregate")
+        .about(
+            "Update the daily and monthly per subreddit rollups in subreddit_activity and \
+             subreddit_top_author",
+        )
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("rebuild").long("rebuild").help(
+                "Recompute every day and month, not only the ones with new comments or posts",
+            ),
+        )
+        .arg(
+            Arg::with_name("top-authors")
+                .long("top-authors")
+                .takes_value(true)
+                .default_value("10")
+                .help("Most active authors to keep per subreddit and period"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let top_authors: usize = matches.value_of("top-authors").unwrap().parse()?;
+    let summary = aggregate::update(&mut db, matches.is_present("rebuild"), top_authors)?;
+    println!(
+        "Rolled up {} new comment(s) and post(s), recomputed {} day(s) and month(s)",
+        summary.new_rows, summary.periods
+    );
+    Ok(())
+}
@@ -0,0 +1,362 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use hashbrown::HashMap;
+
+use super::export;
+use crate::{
+    flair_history::DELETED,
+    output::{self, sparse::Csr, Column, Kind},
+    timestamp,
+    value::Value,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("author-matrix")
+        .about(
+            "Write the author by subreddit comment count and score matrices as Matrix Market and \
+             scipy .npz files, with author and subreddit index files",
+        )
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .required(true)
+                .takes_value(true)
+                .help("Directory to write the matrices and index files to"),
+        )
+        .arg(
+            Arg::with_name("after")
+                .long("after")
+                .takes_value(true)
+                .help("Only count comments made after this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("before")
+                .long("before")
+                .takes_value(true)
+                .help("Only count comments made before this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("min-comments")
+                .long("min-comments")
+                .takes_value(true)
+                .default_value("1")
+                .help("Leave out author and subreddit pairs with fewer comments than this"),
+        )
+        .arg(
+            Arg::with_name("min-author-comments")
+                .long("min-author-comments")
+                .takes_value(true)
+                .default_value("5")
+                .help("Leave out authors with fewer comments than this, over the pairs kept"),
+        )
+        .arg(
+            Arg::with_name("min-subreddit-authors")
+                .long("min-subreddit-authors")
+                .takes_value(true)
+                .default_value("5")
+                .help(
+                    "Leave out subreddits with fewer authors than this, after authors are left out",
+                ),
+        )
+}
+
+// Comment count and score per author and subreddit, leaving out deleted authors.
+const CELLS: &str = "
+SELECT author, subreddit, count(*), sum(score)
+FROM comment
+WHERE author != $1 {conditions}
+GROUP BY author, subreddit
+HAVING count(*) >= $2";
+
+struct Cell {
+    author: u32,
+    subreddit: u32,
+    comments: i64,
+    score: i64,
+}
+
+/// Ids for authors or subreddits, in order of first appearance.
+#[derive(Default)]
+struct Index {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl Index {
+    fn id(&mut self, name: String) -> u32 {
+        if let Some(&id) = self.ids.get(&name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.ids.insert(name.clone(), id);
+        self.names.push(name);
+        id
+    }
+}
+
+/// Totals of one row or column of the matrix.
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    cells: i64,
+    comments: i64,
+    score: i64,
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let min_comments: i64 = matches.value_of("min-comments").unwrap().parse()?;
+    let min_author_comments: i64 = matches.value_of("min-author-comments").unwrap().parse()?;
+    let min_subreddit_authors: i64 = matches.value_of("min-subreddit-authors").unwrap().parse()?;
+    let mut params = vec![
+        Value::Text(DELETED.to_string()),
+        Value::Integer(min_comments),
+    ];
+    let mut conditions = String::new();
+    if let Some(after) = matches.value_of("after") {
+        params.push(Value::Integer(timestamp::parse(after)?));
+        conditions.push_str(&format!(" AND created_utc > ${}", params.len()));
+    }
+    if let Some(before) = matches.value_of("before") {
+        params.push(Value::Integer(timestamp::parse(before)?));
+        conditions.push_str(&format!(" AND created_utc < ${}", params.len()));
+    }
+
+    let mut authors = Index::default();
+    let mut subreddits = Index::default();
+    let mut cells = Vec::new();
+    db.query_each(
+        &CELLS.replace("{conditions}", &conditions),
+        &params,
+        |row| {
+            let mut row = row.into_iter();
+            let (author, subreddit) = match (row.next(), row.next()) {
+                (Some(Value::Text(author)), Some(Value::Text(subreddit))) => (author, subreddit),
+                _ => return Ok(()),
+            };
+            cells.push(Cell {
+                author: authors.id(author),
+                subreddit: subreddits.id(subreddit),
+                comments: row.next().and_then(|value| value.as_i64()).unwrap_or(0),
+                score: row.next().and_then(|value| value.as_i64()).unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    // Authors are judged first, then subreddits by the authors left, and then authors who were
+    // only active in subreddits that were left out are dropped too.
+    let author_totals = totals(&cells, authors.names.len(), |cell| cell.author);
+    cells.retain(|cell| author_totals[cell.author as usize].comments >= min_author_comments);
+    let subreddit_totals = totals(&cells, subreddits.names.len(), |cell| cell.subreddit);
+    cells.retain(|cell| subreddit_totals[cell.subreddit as usize].cells >= min_subreddit_authors);
+    if cells.is_empty() {
+        bail!("No author and subreddit pairs are left after the minimums");
+    }
+
+    // Rows and columns are numbered in name order, so the index files are easy to search.
+    let author_totals = totals(&cells, authors.names.len(), |cell| cell.author);
+    let subreddit_totals = totals(&cells, subreddits.names.len(), |cell| cell.subreddit);
+    let rows = renumber(&authors.names, &author_totals);
+    let columns = renumber(&subreddits.names, &subreddit_totals);
+
+    let dir = Path::new(matches.value_of("output").unwrap());
+    write_index(
+        &dir.join("authors.tsv"),
+        "author",
+        "subreddits",
+        &authors.names,
+        &author_totals,
+        &rows,
+    )?;
+    write_index(
+        &dir.join("subreddits.tsv"),
+        "subreddit",
+        "authors",
+        &subreddits.names,
+        &subreddit_totals,
+        &columns,
+    )?;
+    let (row_count, column_count) = (rows.order.len(), columns.order.len());
+    for name in &["comments", "score"] {
+        let entries = cells
+            .iter()
+            .map(|cell| {
+                let value = if *name == "comments" {
+                    cell.comments
+                } else {
+                    cell.score
+                };
+                (
+                    rows.new[cell.author as usize],
+                    columns.new[cell.subreddit as usize],
+                    value,
+                )
+            })
+            .collect();
+        let matrix = Csr::from_entries(row_count, column_count, entries);
+        let comment = format!(
+            "{} per author (rows, authors.tsv) and subreddit (columns, subreddits.tsv)",
+            name
+        );
+        matrix.write_mtx(&dir.join(format!("{}.mtx", name)), &comment)?;
+        matrix.write_npz(&dir.join(format!("{}.npz", name)))?;
+    }
+    println!(
+        "Wrote {} authors x {} subreddits, {} non-zero entries, to {}",
+        row_count,
+        column_count,
+        cells.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+fn totals(cells: &[Cell], len: usize, key: impl Fn(&Cell) -> u32) -> Vec<Totals> {
+    let mut totals = vec![Totals::default(); len];
+    for cell in cells {
+        let totals = &mut totals[key(cell) as usize];
+        totals.cells += 1;
+        totals.comments += cell.comments;
+        totals.score += cell.score;
+    }
+    totals
+}
+
+/// The ids still in the matrix, numbered again from 0 in name order.
+struct Numbering {
+    /// Old ids in matrix order.
+    order: Vec<u32>,
+    /// New id by old id, for the ones kept.
+    new: Vec<u32>,
+}
+
+fn renumber(names: &[String], totals: &[Totals]) -> Numbering {
+    let mut order: Vec<u32> = (0..names.len() as u32)
+        .filter(|&id| totals[id as usize].cells > 0)
+        .collect();
+    order.sort_unstable_by(|&a, &b| names[a as usize].cmp(&names[b as usize]));
+    let mut new = vec![0; names.len()];
+    for (i, &id) in order.iter().enumerate() {
+        new[id as usize] = i as u32;
+    }
+    Numbering { order, new }
+}
+
+fn write_index(
+    path: &Path,
+    name: &str,
+    count: &str,
+    names: &[String],
+    totals: &[Totals],
+    numbering: &Numbering,
+) -> Result<()> {
+    let columns = vec![
+        Column::new("index", Kind::Integer),
+        Column::new(name, Kind::Text),
+        Column::new(count, Kind::Integer),
+        Column::new("comments", Kind::Integer),
+        Column::new("score", Kind::Integer),
+    ];
+    let mut writer = output::create(path, columns)?;
+    for (i, &id) in numbering.order.iter().enumerate() {
+        let totals = totals[id as usize];
+        writer.write(&[
+            Value::Integer(i as i64),
+            Value::Text(names[id as usize].clone()),
+            Value::Integer(totals.cells),
+            Value::Integer(totals.comments),
+            Value::Integer(totals.score),
+        ])?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::Database,
+        test_support::{self, TempDir},
+    };
+
+    #[test]
+    fn thresholds_and_numbering() {
+        let dir = TempDir::new("author-matrix");
+        let path = dir.path().join("test.db");
+        let mut db = Database::open(path.to_str().unwrap()).unwrap();
+        db.migrate().unwrap();
+        let comments = [
+            ("bob", "rust"),
+            ("alice", "rust"),
+            ("alice", "rust"),
+            ("alice", "rust"),
+            ("bob", "go"),
+            ("bob", "go"),
+            ("carol", "go"),
+            ("[deleted]", "rust"),
+            ("[deleted]", "rust"),
+        ];
+        for (i, (author, subreddit)) in comments.iter().enumerate() {
+            let comment = test_support::comment(&format!("c{}", i), author, subreddit, 2, i as i64);
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+        drop(db);
+
+        let out = dir.path().join("matrix");
+        let matches = subcommand().get_matches_from([
+            "author-matrix",
+            path.to_str().unwrap(),
+            out.to_str().unwrap(),
+            "--min-comments",
+            "1",
+            "--min-author-comments",
+            "2",
+            "--min-subreddit-authors",
+            "2",
+        ]);
+        run(&matches).unwrap();
+
+        // carol has too few comments, and go is left with bob alone.
+        let read = |name: &str| std::fs::read_to_string(out.join(name)).unwrap();
+        assert_eq!(
+            read("authors.tsv"),
+            "index\tauthor\tsubreddits\tcomments\tscore\n0\talice\t1\t3\t6\n1\tbob\t1\t1\t2\n"
+        );
+        assert_eq!(
+            read("subreddits.tsv"),
+            "index\tsubreddit\tauthors\tcomments\tscore\n0\trust\t2\t4\t8\n"
+        );
+        assert!(read("comments.mtx").ends_with("\n2 1 2\n1 1 3\n2 1 1\n"));
+        assert!(read("score.mtx").ends_with("\n2 1 2\n1 1 6\n2 1 2\n"));
+        assert!(out.join("comments.npz").is_file());
+    }
+
+    #[test]
+    fn renumbers_kept_ids_by_name() {
+        let names: Vec<String> = ["c", "a", "b"].iter().map(|s| s.to_string()).collect();
+        let totals = [
+            Totals {
+                cells: 1,
+                ..Totals::default()
+            },
+            Totals::default(),
+            Totals {
+                cells: 2,
+                ..Totals::default()
+            },
+        ];
+        let numbering = renumber(&names, &totals);
+        assert_eq!(numbering.order, [2, 0]);
+        assert_eq!(numbering.new[0], 1);
+        assert_eq!(numbering.new[2], 0);
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::{
+    database::Database,
+    filter::{self, Filter},
+    output::{self, parquet::ParquetOptions, Column, Kind, WriteOptions},
+    value::Value,
+};
+
+/// A table that can be exported, with the columns written and the SQL expressions they are read
+/// from. Reddit ids are written as `id`, like in the dumps.
+pub struct Table {
+    pub name: &'static str,
+    pub columns: &'static [(&'static str, &'static str, Kind)],
+}
+
+pub const COMMENTS: Table = Table {
+    name: "comment",
+    columns: &[
+        ("id", "comment.reddit_id", Kind::Text),
+        ("author", "comment.author", Kind::Text),
+        ("subreddit", "comment.subreddit", Kind::Text),
+        ("body", "comment.body", Kind::Text),
+        ("score", "comment.score", Kind::Integer),
+        ("created_utc", "comment.created_utc", Kind::Integer),
+        ("retrieved_on", "comment.retrieved_on", Kind::Integer),
+        ("parent_id", "comment.parent_id", Kind::Text),
+        ("parent_is_post", "comment.parent_is_post", Kind::Integer),
+        ("flair", "comment.flair", Kind::Integer),
+        ("author_flair_text", "comment.author_flair_text", Kind::Text),
+        ("edited", "comment.edited", Kind::Integer),
+        (
+            "controversiality",
+            "comment.controversiality",
+            Kind::Integer,
+        ),
+    ],
+};
+
+pub const POSTS: Table = Table {
+    name: "posts",
+    columns: &[
+        ("id", "posts.pid", Kind::Text),
+        ("author", "posts.author", Kind::Text),
+        ("subreddit", "posts.subreddit", Kind::Text),
+        ("title", "posts.title", Kind::Text),
+        ("selftext", "posts.selftext", Kind::Text),
+        ("url", "posts.url", Kind::Text),
+        ("score", "posts.score", Kind::Integer),
+        ("created_utc", "posts.created_utc", Kind::Integer),
+        ("retrieved_on", "posts.retrieved_on", Kind::Integer),
+        ("flair", "posts.flair", Kind::Integer),
+        ("author_flair_text", "posts.author_flair_text", Kind::Text),
+        ("edited", "posts.edited", Kind::Integer),
+    ],
+};
+
+impl Table {
+    pub fn by_name(name: &str) -> Result<&'static Table> {
+        match name {
+            "comment" | "comments" => Ok(&COMMENTS),
+            "posts" => Ok(&POSTS),
+            _ => bail!("Unknown table {}, use comment or posts", name),
+        }
+    }
+
+    /// The `SELECT` list and output columns for `names`, or for every column when None.
+    pub fn select(&self, names: Option<Vec<&str>>) -> Result<(String, Vec<Column>)> {
+        let picked: Vec<_> = match names {
+            Some(names) => names
+                .into_iter()
+                .map(|name| {
+                    self.columns
+                        .iter()
+                        .find(|(column, _, _)| *column == name)
+                        .ok_or_else(|| {
+                            let available: Vec<&str> =
+                                self.columns.iter().map(|(column, _, _)| *column).collect();
+                            anyhow!(
+                                "Unknown column {}, available columns are {}",
+                                name,
+                                available.join(", ")
+                            )
+                        })
+                })
+                .collect::<Result<_>>()?,
+            None => self.columns.iter().collect(),
+        };
+        let list = picked
+            .iter()
+            .map(|(name, expression, _)| format!("{} AS {}", expression, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let columns = picked
+            .iter()
+            .map(|(name, _, kind)| Column::new(name, *kind))
+            .collect();
+        Ok((list, columns))
+    }
+}
+
+/// The filter options shared by `export` and `search`.
+pub fn filter_args(app: App<'static, 'static>) -> App<'static, 'static> {
+    filter::args(
+        app.arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("table")
+                .long("table")
+                .takes_value(true)
+                .possible_values(&["comment", "posts"])
+                .default_value("comment")
+                .help("Table to read"),
+        ),
+    )
+    .arg(
+        Arg::with_name("fts-language")
+            .long("fts-language")
+            .takes_value(true)
+            .default_value("english")
+            .help("Postgres text search configuration the query is parsed with"),
+    )
+}
+
+/// `WHERE` clause for `conditions`, empty if there are none.
+pub fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conditions.join(" AND "))
+    }
+}
+
+/// Opens a database to read from. The exported columns come from every migration, so an older
+/// database has to be migrated first.
+pub fn open(target: &str) -> Result<Database> {
+    let mut db = Database::open(target)?;
+    let pending = db.pending_migrations()?;
+    if pending > 0 {
+        bail!(
+            "{} has {} pending migration(s), run `migrate` first",
+            target,
+            pending
+        );
+    }
+    Ok(db)
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    filter_args(
+        SubCommand::with_name("export")
+            .about("Write comments or posts from the database to a NDJSON, CSV, TSV or Parquet file"),
+    )
+    .arg(
+        Arg::with_name("output")
+            .required(true)
+            .takes_value(true)
+            .help("File to write, .json, .csv, .tsv or .parquet; text formats can add .gz, .bz2, .xz or .zst"),
+    )
+    .arg(
+        Arg::with_name("query")
+            .long("query")
+            .takes_value(true)
+            .help("Only rows matching this full text query: FTS5 syntax for Sqlite, websearch_to_tsquery syntax for Postgres"),
+    )
+    .arg(
+        Arg::with_name("columns")
+            .long("columns")
+            .takes_value(true)
+            .multiple(true)
+            .use_delimiter(true)
+            .help("Columns to write, in order [default: all]"),
+    )
+    .arg(
+        Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .help("Write at most this many rows"),
+    )
+    .arg(
+        Arg::with_name("no-header")
+            .long("no-header")
+            .help("Leave out the row of column names in CSV and TSV files"),
+    )
+    .arg(
+        Arg::with_name("compression")
+            .long("compression")
+            .takes_value(true)
+            .help("Parquet compression: none, snappy, gzip or zstd, optionally with a level like zstd:9 [default: snappy]"),
+    )
+    .arg(
+        Arg::with_name("row-group-size")
+            .long("row-group-size")
+            .takes_value(true)
+            .help("Rows per Parquet row group [default: 100000]"),
+    )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = open(matches.value_of("database").unwrap())?;
+    let table = Table::by_name(matches.value_of("table").unwrap())?;
+    let (list, columns) = table.select(matches.values_of("columns").map(Iterator::collect))?;
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    let mut from = table.name.to_string();
+    if let Some(query) = matches.value_of("query") {
+        params.push(Value::Text(query.to_string()));
+        let language = matches.value_of("fts-language").unwrap();
+        let search = db.text_search(table.name, params.len(), language)?;
+        from = search.from;
+        conditions.push(search.condition);
+    }
+    Filter::from_matches(matches)?.add_conditions(table.name, &mut conditions, &mut params);
+    let mut sql = format!("SELECT {} FROM {}{}", list, from, where_clause(&conditions));
+    if let Some(limit) = matches.value_of("limit") {
+        let limit: u64 = limit.parse()?;
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    let mut options = WriteOptions {
+        header: !matches.is_present("no-header"),
+        ..WriteOptions::default()
+    };
+    if let Some(size) = matches.value_of("row-group-size") {
+        options.parquet.row_group_size = size.parse()?;
+    }
+    if let Some(compression) = matches.value_of("compression") {
+        options.parquet.compression = ParquetOptions::parse_compression(compression)?;
+    }
+    let path = matches.value_of("output").unwrap();
+    let mut writer = output::create_with(Path::new(path), columns, &options)?;
+    db.query_each(&sql, &params, |row| writer.write(&row))?;
+    let rows = writer.finish()?;
+    println!("Wrote {} rows to {}", rows, path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    #[test]
+    fn picks_columns() {
+        let (list, columns) = COMMENTS.select(Some(vec!["id", "score"])).unwrap();
+        assert_eq!(list, "comment.reddit_id AS id, comment.score AS score");
+        assert_eq!(columns.len(), 2);
+        let err = POSTS.select(Some(vec!["body"])).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Unknown column body, available columns are id, "));
+        assert!(Table::by_name("users").is_err());
+    }
+
+    #[test]
+    fn exports_filtered_rows() {
+        let dir = TempDir::new("export");
+        let path = dir.path().join("test.db");
+        let mut db = Database::open(path.to_str().unwrap()).unwrap();
+        db.migrate().unwrap();
+        let comments = [
+            ("a", "alice", "rust", "snoo says hi", 10),
+            ("b", "bob", "rust", "snoo again", 20),
+            ("c", "bob", "go", "snoo in go", 30),
+            ("d", "carol", "rust", "nothing here", 40),
+            ("e", "alice", "go", "snoo late", 50),
+        ];
+        for (id, author, subreddit, body, created_utc) in &comments {
+            let mut comment = test_support::comment(id, author, subreddit, 1, *created_utc);
+            comment.body = body.to_string();
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+        drop(db);
+
+        let export = |args: &[&str]| {
+            let out = dir.path().join("out.csv");
+            let matches = subcommand().get_matches_from(
+                [
+                    "export",
+                    path.to_str().unwrap(),
+                    out.to_str().unwrap(),
+                    "--columns",
+                    "id,author",
+                ]
+                .iter()
+                .chain(args),
+            );
+            run(&matches).unwrap();
+            std::fs::read_to_string(out).unwrap()
+        };
+        // Either filter matches, the time range and the query always have to.
+        assert_eq!(
+            export(&["--username", "alice", "--subreddit", "go", "--before", "50"]),
+            "id,author\na,alice\nc,bob\n"
+        );
+        assert_eq!(
+            export(&["--subreddit", "rust", "--query", "snoo"]),
+            "id,author\na,alice\nb,bob\n"
+        );
+        assert_eq!(
+            export(&["--after", "20", "--limit", "1", "--no-header"]),
+            "c,bob\n"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use super::export;
+use crate::{
+    flair_history::DELETED,
+    output::{self, Column, Kind},
+    timestamp,
+    value::Value,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("build-flair-dataset")
+        .about(
+            "Build a flair prediction dataset: comment counts and score sums per subreddit for \
+             every author with a single flair in the target subreddit",
+        )
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("subreddit")
+                .long("subreddit")
+                .takes_value(true)
+                .default_value("PoliticalCompassMemes")
+                .help("Subreddit whose flair is predicted"),
+        )
+        .arg(
+            Arg::with_name("after")
+                .long("after")
+                .takes_value(true)
+                .help("Only use comments made after this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("before")
+                .long("before")
+                .takes_value(true)
+                .help("Only use comments made before this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("min-support")
+                .long("min-support")
+                .takes_value(true)
+                .default_value("50")
+                .help("Leave out subreddits with fewer flaired authors than this"),
+        )
+        .arg(
+            Arg::with_name("table")
+                .long("table")
+                .takes_value(true)
+                .conflicts_with("output")
+                .help("Table to write the dataset to, replacing it [default: <subreddit>_flair_prediction]"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Write the dataset to a .csv, .tsv, .json or .parquet file instead of a table"),
+        )
+}
+
+// One row per author and subreddit. Authors are labelled with their flair code in the target
+// subreddit; authors that used more than one code there are left out, as are subreddits with
+// too few labelled authors.
+const DATASET: &str = "
+WITH labels AS (
+    SELECT author, min(flair) AS flair FROM comment
+    WHERE subreddit = $1 AND created_utc > $2 AND created_utc < $3
+      AND flair IS NOT NULL AND flair != 0 AND author != $5
+    GROUP BY author
+    HAVING count(DISTINCT flair) = 1
+), activity AS (
+    SELECT c.author, c.subreddit, count(*) AS comments, sum(c.score) AS score
+    FROM comment c JOIN labels l ON l.author = c.author
+    WHERE c.created_utc > $2 AND c.created_utc < $3
+    GROUP BY c.author, c.subreddit
+), supported AS (
+    SELECT subreddit FROM activity GROUP BY subreddit HAVING count(*) >= $4
+)
+SELECT a.author, a.subreddit, l.flair, a.comments, a.score
+FROM activity a JOIN labels l ON l.author = a.author
+WHERE a.subreddit IN (SELECT subreddit FROM supported)
+ORDER BY a.author, a.subreddit";
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let subreddit = matches.value_of("subreddit").unwrap();
+    let after = matches.value_of("after").map_or(Ok(0), timestamp::parse)?;
+    let before = matches
+        .value_of("before")
+        .map_or(Ok(i32::MAX.into()), timestamp::parse)?;
+    let min_support: i64 = matches.value_of("min-support").unwrap().parse()?;
+    let params = [
+        Value::Text(subreddit.to_string()),
+        Value::Integer(after),
+        Value::Integer(before),
+        Value::Integer(min_support),
+        Value::Text(DELETED.to_string()),
+    ];
+    let columns = vec![
+        Column::new("author", Kind::Text),
+        Column::new("subreddit", Kind::Text),
+        Column::new("flair", Kind::Integer),
+        Column::new("comments", Kind::Integer),
+        Column::new("score", Kind::Integer),
+    ];
+    if let Some(path) = matches.value_of("output") {
+        let mut writer = output::create(Path::new(path), columns)?;
+        db.query_each(DATASET, &params, |row| writer.write(&row))?;
+        let rows = writer.finish()?;
+        println!("Wrote {} rows to {}", rows, path);
+        return Ok(());
+    }
+    let table = match matches.value_of("table") {
+        Some(table) => table.to_string(),
+        None => format!("{}_flair_prediction", subreddit.to_lowercase()),
+    };
+    if !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("Invalid table name {:?}", table);
+    }
+    db.create_table(&table, &columns)?;
+    let names: Vec<&str> = columns.iter().map(|column| column.name.as_str()).collect();
+    let rows = db.execute(
+        &format!("INSERT INTO {} ({}) {}", table, names.join(", "), DATASET),
+        &params,
+    )?;
+    println!("Wrote {} rows to table {}", rows, table);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::Database, flair::Flair, test_support};
+
+    const PCM: &str = "PoliticalCompassMemes";
+
+    fn dataset(after: i64, before: i64) -> Vec<Vec<Value>> {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        let comments = [
+            ("alice", PCM, Some(1)),
+            ("alice", PCM, Some(1)),
+            ("alice", "rust", None),
+            ("alice", "go", None),
+            ("bob", PCM, Some(4)),
+            ("bob", "rust", None),
+            // Two different flairs, so no label.
+            ("carol", PCM, Some(1)),
+            ("carol", PCM, Some(4)),
+            ("carol", "rust", None),
+            // Unmapped flair.
+            ("dave", PCM, Some(0)),
+            ("dave", "rust", None),
+            ("[deleted]", PCM, Some(2)),
+        ];
+        for (i, (author, subreddit, code)) in comments.iter().enumerate() {
+            let mut comment =
+                test_support::comment(&format!("c{}", i), author, subreddit, 2, i as i64 + 1);
+            comment.flair = Flair {
+                code: *code,
+                label: None,
+            };
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+
+        let params = [
+            Value::Text(PCM.to_string()),
+            Value::Integer(after),
+            Value::Integer(before),
+            Value::Integer(2),
+            Value::Text(DELETED.to_string()),
+        ];
+        let mut rows = Vec::new();
+        db.query_each(DATASET, &params, |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .unwrap();
+        rows
+    }
+
+    fn row(author: &str, subreddit: &str, flair: i64, comments: i64) -> Vec<Value> {
+        vec![
+            Value::Text(author.to_string()),
+            Value::Text(subreddit.to_string()),
+            Value::Integer(flair),
+            Value::Integer(comments),
+            Value::Integer(comments * 2),
+        ]
+    }
+
+    #[test]
+    fn labelled_authors_in_supported_subreddits() {
+        assert_eq!(
+            dataset(0, 100),
+            [
+                row("alice", PCM, 1, 2),
+                row("alice", "rust", 1, 1),
+                row("bob", PCM, 4, 1),
+                row("bob", "rust", 4, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn time_range() {
+        // Of the PCM comments only bob's and carol's first are in range, and rust is left with
+        // bob alone.
+        assert_eq!(
+            dataset(2, 8),
+            [row("bob", PCM, 4, 1), row("carol", PCM, 1, 1),]
+        );
+    }
+}
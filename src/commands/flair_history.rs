@@ -0,0 +1,38 @@
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use super::export;
+use crate::flair_history::print_changes;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("flair-history")
+        .about("Report authors that have used more than one flair in a subreddit")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("subreddit")
+                .long("subreddit")
+                .takes_value(true)
+                .help("Only report authors in this subreddit"),
+        )
+        .arg(
+            Arg::with_name("rebuild")
+                .long("rebuild")
+                .help("Recompute author_flair_history from the comment table first"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    if matches.is_present("rebuild") {
+        let rows = db.rebuild_flair_history()?;
+        println!("author_flair_history rebuilt with {} rows", rows);
+    }
+    let changes = db.flair_changes(matches.value_of("subreddit"))?;
+    print_changes(&changes);
+    Ok(())
+}
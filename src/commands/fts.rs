@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use rusqlite::{Connection, OpenFlags};
+
+use crate::sqlite::fts::{FtsTable, COMMENT_FTS, POSTS_FTS};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("fts")
+        .about("Check the FTS5 indexes of a Sqlite database against the comment and posts tables")
+        .arg(
+            Arg::with_name("sqlite-file")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite database to check"),
+        )
+        .arg(
+            Arg::with_name("rebuild")
+                .long("rebuild")
+                .help("Rebuild the indexes from scratch, then check them again"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let path = Path::new(matches.value_of("sqlite-file").unwrap());
+    // Opening without SQLITE_OPEN_CREATE, so a mistyped path isn't checked as an empty database.
+    if !path.is_file() {
+        bail!("{} does not exist", path.display());
+    }
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    let rebuild = matches.is_present("rebuild");
+    let mut out_of_sync = Vec::new();
+    for table in &[COMMENT_FTS, POSTS_FTS] {
+        if !table.exists(&connection)? {
+            println!("{}: not present, skipping", table.name);
+            continue;
+        }
+        if rebuild {
+            println!("{}: rebuilding from {}", table.name, table.content);
+            table.rebuild(&connection)?;
+        }
+        if !check(&connection, table)? {
+            out_of_sync.push(table.name);
+        }
+    }
+    if !out_of_sync.is_empty() {
+        bail!(
+            "{} out of sync, run with --rebuild to fix",
+            out_of_sync.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn check(connection: &Connection, table: &FtsTable) -> Result<bool> {
+    let report = table.check(connection)?;
+    match &report.integrity_error {
+        None => println!("{}: integrity-check ok", table.name),
+        Some(err) => println!("{}: integrity-check failed: {}", table.name, err),
+    }
+    println!(
+        "{}: {} rows of {} missing from the index, {} index entries without a row",
+        table.name, report.missing, table.content, report.orphaned
+    );
+    if report.broken_trigger {
+        println!(
+            "{}: comment_au trigger indexes the subreddit as the body of updated comments, \
+             run `migrate` to replace it",
+            table.name
+        );
+    }
+    Ok(report.is_ok())
+}
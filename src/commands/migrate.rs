@@ -0,0 +1,22 @@
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::database::Database;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("migrate")
+        .about("Apply pending schema migrations")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = Database::open(matches.value_of("database").unwrap())?;
+    let applied = db.migrate()?;
+    println!("Applied {} migration(s)", applied);
+    Ok(())
+}
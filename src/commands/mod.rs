@@ -0,0 +1,62 @@
+//! Subcommands other than the default import.
+//!
+//! Each module exposes `subcommand()`, its clap definition, and `run()`, which is handed that
+//! subcommand's matches.
+
+pub mod aggregate;
+pub mod author_matrix;
+pub mod export;
+pub mod flair_dataset;
+pub mod flair_history;
+pub mod fts;
+pub mod migrate;
+pub mod overlap;
+pub mod profile;
+pub mod reply_graph;
+pub mod search;
+pub mod serve;
+pub mod status;
+pub mod thread;
+
+use anyhow::Result;
+use clap::{App, ArgMatches};
+
+pub fn subcommands() -> Vec<App<'static, 'static>> {
+    vec![
+        aggregate::subcommand(),
+        author_matrix::subcommand(),
+        export::subcommand(),
+        flair_dataset::subcommand(),
+        flair_history::subcommand(),
+        fts::subcommand(),
+        migrate::subcommand(),
+        overlap::subcommand(),
+        profile::subcommand(),
+        reply_graph::subcommand(),
+        search::subcommand(),
+        serve::subcommand(),
+        status::subcommand(),
+        thread::subcommand(),
+    ]
+}
+
+/// Runs the subcommand in `matches`, if there is one.
+pub fn run(matches: &ArgMatches) -> Option<Result<()>> {
+    Some(match matches.subcommand() {
+        ("aggregate", Some(matches)) => aggregate::run(matches),
+        ("author-matrix", Some(matches)) => author_matrix::run(matches),
+        ("build-flair-dataset", Some(matches)) => flair_dataset::run(matches),
+        ("export", Some(matches)) => export::run(matches),
+        ("flair-history", Some(matches)) => flair_history::run(matches),
+        ("fts", Some(matches)) => fts::run(matches),
+        ("migrate", Some(matches)) => migrate::run(matches),
+        ("overlap", Some(matches)) => overlap::run(matches),
+        ("profile", Some(matches)) => profile::run(matches),
+        ("reply-graph", Some(matches)) => reply_graph::run(matches),
+        ("search", Some(matches)) => search::run(matches),
+        ("serve", Some(matches)) => serve::run(matches),
+        ("status", Some(matches)) => status::run(matches),
+        ("thread", Some(matches)) => thread::run(matches),
+        _ => return None,
+    })
+}
@@ -0,0 +1,355 @@
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use super::export;
+use crate::{
+    database::Database,
+    flair_history::DELETED,
+    output::{json_object, Column, Kind},
+    timestamp,
+    value::Value,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("overlap")
+        .about("List the subreddits whose active authors overlap most with those of a subreddit")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("subreddit")
+                .required(true)
+                .takes_value(true)
+                .help("Subreddit to compare the others with, without r/"),
+        )
+        .arg(
+            Arg::with_name("after")
+                .long("after")
+                .takes_value(true)
+                .help("Only count comments made after this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("before")
+                .long("before")
+                .takes_value(true)
+                .help("Only count comments made before this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("min-comments")
+                .long("min-comments")
+                .takes_value(true)
+                .default_value("3")
+                .help("Comments an author needs in a subreddit to count as active there"),
+        )
+        .arg(
+            Arg::with_name("min-shared")
+                .long("min-shared")
+                .takes_value(true)
+                .default_value("5")
+                .help("Leave out subreddits sharing fewer active authors than this"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(&["jaccard", "lift", "pmi", "shared"])
+                .default_value("jaccard")
+                .help("Order subreddits by this measure, highest first"),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .takes_value(true)
+                .default_value("25")
+                .help("Subreddits to list"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["table", "json"])
+                .default_value("table")
+                .help("Print a table, or one JSON object per subreddit"),
+        )
+}
+
+// Authors with enough comments in a subreddit, leaving out deleted ones.
+const ACTIVE: &str = "
+WITH active AS (
+    SELECT author, subreddit FROM comment
+    WHERE author != $1 {conditions}
+    GROUP BY author, subreddit
+    HAVING count(*) >= $2
+)";
+
+const TOTAL: &str = "SELECT count(DISTINCT author) FROM active";
+
+// Active authors of every subreddit sharing at least one with the target subreddit.
+const SHARED: &str = "
+SELECT o.subreddit, count(*), sizes.authors
+FROM active t
+JOIN active o ON o.author = t.author AND o.subreddit != t.subreddit
+JOIN (SELECT subreddit, count(*) AS authors FROM active GROUP BY subreddit) sizes ON sizes.subreddit = o.subreddit
+WHERE t.subreddit = {target}
+GROUP BY o.subreddit, sizes.authors";
+
+const TARGET: &str = "SELECT count(*) FROM active WHERE subreddit = {target}";
+
+#[derive(Debug)]
+struct Overlap {
+    subreddit: String,
+    shared: i64,
+    authors: i64,
+    jaccard: f64,
+    lift: f64,
+    pmi: f64,
+}
+
+impl Overlap {
+    /// Measures for a subreddit with `authors` active authors, `shared` of them also active in
+    /// the target subreddit, which has `target` of the `total` active authors.
+    fn new(subreddit: String, shared: i64, authors: i64, target: i64, total: i64) -> Self {
+        let lift = (shared * total) as f64 / (target * authors) as f64;
+        Overlap {
+            subreddit,
+            shared,
+            authors,
+            jaccard: shared as f64 / (target + authors - shared) as f64,
+            lift,
+            pmi: lift.log2(),
+        }
+    }
+}
+
+/// The active authors of `subreddit` and of all subreddits, and the subreddits sharing at least
+/// `--min-shared` of them, sorted and limited as asked.
+struct Report {
+    subreddit: String,
+    target: i64,
+    total: i64,
+    overlaps: Vec<Overlap>,
+}
+
+fn report(db: &mut Database, matches: &ArgMatches) -> Result<Report> {
+    let subreddit = matches.value_of("subreddit").unwrap();
+    let subreddit = subreddit
+        .trim_start_matches('/')
+        .trim_start_matches("r/")
+        .to_string();
+    let min_comments: i64 = matches.value_of("min-comments").unwrap().parse()?;
+    let min_shared: i64 = matches.value_of("min-shared").unwrap().parse()?;
+    let limit: usize = matches.value_of("limit").unwrap().parse()?;
+    let mut params = vec![
+        Value::Text(DELETED.to_string()),
+        Value::Integer(min_comments),
+    ];
+    let mut conditions = String::new();
+    if let Some(after) = matches.value_of("after") {
+        params.push(Value::Integer(timestamp::parse(after)?));
+        conditions.push_str(&format!(" AND created_utc > ${}", params.len()));
+    }
+    if let Some(before) = matches.value_of("before") {
+        params.push(Value::Integer(timestamp::parse(before)?));
+        conditions.push_str(&format!(" AND created_utc < ${}", params.len()));
+    }
+    let active = ACTIVE.replace("{conditions}", &conditions);
+    let total_params = params.clone();
+    params.push(Value::Text(subreddit.clone()));
+    let target_param = format!("${}", params.len());
+    let sql = |query: &str| format!("{}\n{}", active, query.replace("{target}", &target_param));
+
+    let mut count = |sql: String, params: &[Value]| -> Result<i64> {
+        let mut count = 0;
+        db.query_each(&sql, params, |row| {
+            count = row[0].as_i64().unwrap_or(0);
+            Ok(())
+        })?;
+        Ok(count)
+    };
+    let total = count(sql(TOTAL), &total_params)?;
+    let target = count(sql(TARGET), &params)?;
+    if target == 0 {
+        bail!(
+            "r/{} has no authors with {} or more comments",
+            subreddit,
+            min_comments
+        );
+    }
+
+    let mut overlaps = Vec::new();
+    db.query_each(&sql(SHARED), &params, |row| {
+        let shared = row[1].as_i64().unwrap_or(0);
+        let authors = row[2].as_i64().unwrap_or(0);
+        if shared < min_shared {
+            return Ok(());
+        }
+        let subreddit = match &row[0] {
+            Value::Text(s) => s.clone(),
+            _ => String::new(),
+        };
+        overlaps.push(Overlap::new(subreddit, shared, authors, target, total));
+        Ok(())
+    })?;
+    let sort = matches.value_of("sort").unwrap();
+    overlaps.sort_by(|a, b| {
+        let key = |o: &Overlap| match sort {
+            "lift" => o.lift,
+            "pmi" => o.pmi,
+            "shared" => o.shared as f64,
+            _ => o.jaccard,
+        };
+        key(b)
+            .total_cmp(&key(a))
+            .then_with(|| a.subreddit.cmp(&b.subreddit))
+    });
+    overlaps.truncate(limit);
+    Ok(Report {
+        subreddit,
+        target,
+        total,
+        overlaps,
+    })
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let Report {
+        subreddit,
+        target,
+        total,
+        overlaps,
+    } = report(&mut db, matches)?;
+    let min_shared: i64 = matches.value_of("min-shared").unwrap().parse()?;
+
+    if matches.value_of("format") == Some("json") {
+        let columns = [
+            Column::new("subreddit", Kind::Text),
+            Column::new("shared", Kind::Integer),
+            Column::new("authors", Kind::Integer),
+            Column::new("jaccard", Kind::Real),
+            Column::new("lift", Kind::Real),
+            Column::new("pmi", Kind::Real),
+        ];
+        for o in &overlaps {
+            let row = [
+                Value::Text(o.subreddit.clone()),
+                Value::Integer(o.shared),
+                Value::Integer(o.authors),
+                Value::Real(o.jaccard),
+                Value::Real(o.lift),
+                Value::Real(o.pmi),
+            ];
+            println!("{}", json_object(&columns, &row));
+        }
+        return Ok(());
+    }
+    println!(
+        "r/{}: {} active author(s) of {} overall",
+        subreddit, target, total
+    );
+    if overlaps.is_empty() {
+        println!("No subreddits share {} or more of them", min_shared);
+        return Ok(());
+    }
+    println!(
+        "{:<24} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "subreddit", "shared", "authors", "jaccard", "lift", "pmi"
+    );
+    for o in &overlaps {
+        println!(
+            "{:<24} {:>8} {:>8} {:>8.4} {:>8.2} {:>8.2}",
+            o.subreddit, o.shared, o.authors, o.jaccard, o.lift, o.pmi
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn report(args: &[&str]) -> Report {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        let memberships = [
+            ("a", &["u1", "u2", "u3", "u4", "[deleted]"][..]),
+            ("b", &["u1", "u2", "u5", "[deleted]"]),
+            ("c", &["u3"]),
+            ("d", &["u6"]),
+        ];
+        let mut id = 0;
+        for (subreddit, authors) in &memberships {
+            for author in authors.iter() {
+                id += 1;
+                let comment = test_support::comment(&format!("c{}", id), author, subreddit, 1, id);
+                db.insert_comment(&comment).unwrap();
+            }
+        }
+        db.commit().unwrap();
+        let matches = subcommand().get_matches_from(
+            ["overlap", ":memory:", "r/a", "--min-comments", "1"]
+                .iter()
+                .chain(args),
+        );
+        super::report(&mut db, &matches).unwrap()
+    }
+
+    fn subreddits(report: &Report) -> Vec<&str> {
+        report
+            .overlaps
+            .iter()
+            .map(|o| o.subreddit.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn measures() {
+        let o = Overlap::new("b".to_string(), 2, 3, 4, 6);
+        assert_eq!(o.jaccard, 0.4);
+        assert_eq!(o.lift, 1.0);
+        assert_eq!(o.pmi, 0.0);
+        let o = Overlap::new("c".to_string(), 1, 1, 4, 6);
+        assert_eq!(o.jaccard, 0.25);
+        assert_eq!(o.lift, 1.5);
+        assert_eq!(o.pmi, 1.5f64.log2());
+    }
+
+    #[test]
+    fn overlapping_subreddits() {
+        let report = report(&["--min-shared", "1"]);
+        assert_eq!(report.subreddit, "a");
+        // Deleted authors don't count.
+        assert_eq!((report.target, report.total), (4, 6));
+        assert_eq!(subreddits(&report), ["b", "c"]);
+        let b = &report.overlaps[0];
+        assert_eq!((b.shared, b.authors, b.jaccard, b.lift), (2, 3, 0.4, 1.0));
+    }
+
+    #[test]
+    fn sort_and_filters() {
+        assert_eq!(
+            subreddits(&report(&["--min-shared", "1", "--sort", "lift"])),
+            ["c", "b"]
+        );
+        assert_eq!(subreddits(&report(&["--min-shared", "2"])), ["b"]);
+        assert_eq!(
+            subreddits(&report(&[
+                "--min-shared",
+                "1",
+                "--limit",
+                "1",
+                "--sort",
+                "pmi"
+            ])),
+            ["c"]
+        );
+        // u1 and u2 only commented in b after their comments in a.
+        let report = report(&["--min-shared", "1", "--after", "2"]);
+        assert_eq!((report.target, report.total), (2, 6));
+        assert_eq!(subreddits(&report), ["c"]);
+    }
+}
@@ -0,0 +1,399 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use hashbrown::HashMap;
+use serde_json::json;
+
+use super::export;
+use crate::{database::Database, timestamp, value::Value};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("profile")
+        .about("Summarize the activity of one author: when, where, scores, flair and active hours")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("username")
+                .required(true)
+                .takes_value(true)
+                .help("Author to profile, without u/"),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .takes_value(true)
+                .default_value("20")
+                .help("Subreddits to list, most active first, 0 for all"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["report", "json"])
+                .default_value("report")
+                .help("Print a readable report or a JSON object"),
+        )
+}
+
+const COMMENTS: &str =
+    "SELECT subreddit, score, created_utc, length(body) FROM comment WHERE author = $1";
+
+const POSTS: &str = "SELECT subreddit, score, created_utc FROM posts WHERE author = $1";
+
+const FLAIR_HISTORY: &str = "
+SELECT h.subreddit, coalesce(f.label, f.text), h.first_seen, h.last_seen, h.comment_count
+FROM author_flair_history h JOIN flair f ON f.id = h.flair_id
+WHERE h.author = $1
+ORDER BY h.subreddit, h.first_seen";
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Comment or post count and score total.
+#[derive(Default, Clone, Copy)]
+struct Activity {
+    count: i64,
+    score: i64,
+}
+
+#[derive(Default)]
+struct SubredditActivity {
+    name: String,
+    comments: Activity,
+    posts: Activity,
+}
+
+struct FlairSpan {
+    subreddit: String,
+    flair: String,
+    first_seen: i64,
+    last_seen: i64,
+    comment_count: i64,
+}
+
+struct Profile {
+    author: String,
+    first_seen: i64,
+    last_seen: i64,
+    comments: Activity,
+    posts: Activity,
+    /// Characters per comment.
+    average_length: f64,
+    /// Most active first.
+    subreddits: Vec<SubredditActivity>,
+    /// More subreddits than were kept in `subreddits`.
+    more_subreddits: usize,
+    flair_history: Vec<FlairSpan>,
+    /// Comments and posts per hour of the day and per weekday, Monday first, in UTC.
+    hours: [i64; 24],
+    weekdays: [i64; 7],
+}
+
+/// Everything the report shows about the author given on the command line.
+fn profile(db: &mut Database, matches: &ArgMatches) -> Result<Profile> {
+    let author = matches.value_of("username").unwrap();
+    let author = author
+        .trim_start_matches('/')
+        .trim_start_matches("u/")
+        .to_string();
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let params = [Value::Text(author.clone())];
+
+    let mut profile = Profile {
+        author,
+        first_seen: i64::MAX,
+        last_seen: i64::MIN,
+        comments: Activity::default(),
+        posts: Activity::default(),
+        average_length: 0.0,
+        subreddits: Vec::new(),
+        more_subreddits: 0,
+        flair_history: Vec::new(),
+        hours: [0; 24],
+        weekdays: [0; 7],
+    };
+    let mut subreddits: HashMap<String, SubredditActivity> = HashMap::new();
+    let mut length = 0;
+    for (sql, is_post) in &[(COMMENTS, false), (POSTS, true)] {
+        db.query_each(sql, &params, |row| {
+            let subreddit = subreddits.entry(row[0].to_text()).or_default();
+            let score = row[1].as_i64().unwrap_or(0);
+            let (total, in_subreddit) = if *is_post {
+                (&mut profile.posts, &mut subreddit.posts)
+            } else {
+                length += row[3].as_i64().unwrap_or(0);
+                (&mut profile.comments, &mut subreddit.comments)
+            };
+            for activity in [total, in_subreddit] {
+                activity.count += 1;
+                activity.score += score;
+            }
+            if let Value::Integer(created_utc) = row[2] {
+                profile.first_seen = profile.first_seen.min(created_utc);
+                profile.last_seen = profile.last_seen.max(created_utc);
+                profile.hours[created_utc.rem_euclid(86_400) as usize / 3_600] += 1;
+                // 1970-01-01 was a Thursday.
+                profile.weekdays[(created_utc.div_euclid(86_400) + 3).rem_euclid(7) as usize] += 1;
+            }
+            Ok(())
+        })?;
+    }
+    if profile.comments.count == 0 && profile.posts.count == 0 {
+        bail!("No comments or posts by {}", profile.author);
+    }
+    if profile.comments.count > 0 {
+        profile.average_length = length as f64 / profile.comments.count as f64;
+    }
+
+    let mut subreddits: Vec<SubredditActivity> = subreddits
+        .into_iter()
+        .map(|(name, activity)| SubredditActivity { name, ..activity })
+        .collect();
+    subreddits.sort_by(|a, b| {
+        (b.comments.count + b.posts.count)
+            .cmp(&(a.comments.count + a.posts.count))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    if top > 0 && subreddits.len() > top {
+        profile.more_subreddits = subreddits.len() - top;
+        subreddits.truncate(top);
+    }
+    profile.subreddits = subreddits;
+
+    db.query_each(FLAIR_HISTORY, &params, |row| {
+        profile.flair_history.push(FlairSpan {
+            subreddit: row[0].to_text(),
+            flair: row[1].to_text(),
+            first_seen: row[2].as_i64().unwrap_or(0),
+            last_seen: row[3].as_i64().unwrap_or(0),
+            comment_count: row[4].as_i64().unwrap_or(0),
+        });
+        Ok(())
+    })?;
+    Ok(profile)
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let profile = profile(&mut db, matches)?;
+    let output = match matches.value_of("format").unwrap() {
+        "json" => serde_json::to_string_pretty(&to_json(&profile))?,
+        _ => render_report(&profile),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+fn to_json(profile: &Profile) -> serde_json::Value {
+    let activity = |activity: &Activity| json!({"count": activity.count, "score": activity.score});
+    json!({
+        "author": profile.author,
+        "first_seen": profile.first_seen,
+        "last_seen": profile.last_seen,
+        "comments": {
+            "count": profile.comments.count,
+            "score": profile.comments.score,
+            "average_length": profile.average_length,
+        },
+        "posts": activity(&profile.posts),
+        "subreddits": profile.subreddits.iter().map(|subreddit| json!({
+            "subreddit": subreddit.name,
+            "comments": activity(&subreddit.comments),
+            "posts": activity(&subreddit.posts),
+        })).collect::<Vec<_>>(),
+        "more_subreddits": profile.more_subreddits,
+        "flair_history": profile.flair_history.iter().map(|span| json!({
+            "subreddit": span.subreddit,
+            "flair": span.flair,
+            "first_seen": span.first_seen,
+            "last_seen": span.last_seen,
+            "comment_count": span.comment_count,
+        })).collect::<Vec<_>>(),
+        "hours": profile.hours,
+        "weekdays": WEEKDAYS.iter().zip(profile.weekdays.iter()).map(|(day, count)| (day.to_string(), json!(count))).collect::<serde_json::Map<_, _>>(),
+    })
+}
+
+/// One line per bucket, with a bar scaled to the largest one.
+fn histogram(out: &mut String, labels: &[String], counts: &[i64]) {
+    const WIDTH: i64 = 40;
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    for (label, &count) in labels.iter().zip(counts) {
+        let bar = "#".repeat(((count * WIDTH + max - 1) / max) as usize);
+        let _ = writeln!(out, "  {:>3}  {:<40} {}", label, bar, count);
+    }
+}
+
+fn render_report(profile: &Profile) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "u/{}", profile.author);
+    let _ = writeln!(
+        out,
+        "First seen {}, last seen {}",
+        timestamp::date(profile.first_seen),
+        timestamp::date(profile.last_seen)
+    );
+    let _ = writeln!(
+        out,
+        "{} comment(s), {} points, {:.0} characters on average",
+        profile.comments.count, profile.comments.score, profile.average_length
+    );
+    let _ = writeln!(
+        out,
+        "{} post(s), {} points",
+        profile.posts.count, profile.posts.score
+    );
+
+    let _ = writeln!(out, "\nSubreddits");
+    let width = profile
+        .subreddits
+        .iter()
+        .map(|subreddit| subreddit.name.len())
+        .max()
+        .unwrap_or(0);
+    for subreddit in &profile.subreddits {
+        let _ = writeln!(
+            out,
+            "  {:<width$}  {:>6} comment(s) {:>8} points  {:>5} post(s) {:>8} points",
+            subreddit.name,
+            subreddit.comments.count,
+            subreddit.comments.score,
+            subreddit.posts.count,
+            subreddit.posts.score,
+            width = width
+        );
+    }
+    if profile.more_subreddits > 0 {
+        let _ = writeln!(out, "  ... and {} more", profile.more_subreddits);
+    }
+
+    if !profile.flair_history.is_empty() {
+        let _ = writeln!(out, "\nFlair");
+        for span in &profile.flair_history {
+            let _ = writeln!(
+                out,
+                "  r/{}: {} from {} to {}, {} comment(s)",
+                span.subreddit,
+                span.flair,
+                timestamp::date(span.first_seen),
+                timestamp::date(span.last_seen),
+                span.comment_count
+            );
+        }
+    }
+
+    let _ = writeln!(out, "\nHour of day (UTC)");
+    let hours: Vec<String> = (0..24).map(|hour| format!("{:02}", hour)).collect();
+    histogram(&mut out, &hours, &profile.hours);
+    let _ = writeln!(out, "\nWeekday (UTC)");
+    let weekdays: Vec<String> = WEEKDAYS.iter().map(|day| day.to_string()).collect();
+    histogram(&mut out, &weekdays, &profile.weekdays);
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{comment::Comment, test_support};
+
+    // Friday 2019-06-21, 00:00 UTC.
+    const FRIDAY: i64 = 1561075200;
+
+    fn comment(
+        id: &str,
+        author: &str,
+        subreddit: &str,
+        body: &str,
+        score: i64,
+        created_utc: i64,
+    ) -> Comment {
+        let mut comment = test_support::comment(id, author, subreddit, score, created_utc);
+        comment.author_flair_text = Some("Rustacean".to_string());
+        comment.body = body.to_string();
+        comment
+    }
+
+    fn profile(args: &[&str]) -> serde_json::Value {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        for comment in &[
+            comment("a", "alice", "rust", "hello", 3, FRIDAY + 8 * 3600),
+            comment("b", "alice", "rust", "abc", 1, FRIDAY + 8 * 3600 + 60),
+            comment("c", "alice", "go", "", 5, FRIDAY + 86_400 + 23 * 3600),
+            comment("d", "bob", "rust", "not alice", 100, FRIDAY),
+        ] {
+            db.insert_comment(comment).unwrap();
+        }
+        db.insert_post(&test_support::post(
+            "post",
+            "alice",
+            "go",
+            10,
+            FRIDAY - 86_400,
+        ))
+        .unwrap();
+        db.commit().unwrap();
+        let matches = subcommand().get_matches_from(["profile", ":memory:"].iter().chain(args));
+        to_json(&super::profile(&mut db, &matches).unwrap())
+    }
+
+    #[test]
+    fn summarizes_an_author() {
+        let profile = profile(&["u/alice"]);
+        assert_eq!(profile["first_seen"], FRIDAY - 86_400);
+        assert_eq!(profile["last_seen"], FRIDAY + 86_400 + 23 * 3600);
+        assert_eq!(
+            profile["comments"],
+            serde_json::json!({"count": 3, "score": 9, "average_length": 8.0 / 3.0})
+        );
+        assert_eq!(
+            profile["posts"],
+            serde_json::json!({"count": 1, "score": 10})
+        );
+        let subreddits: Vec<_> = profile["subreddits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["subreddit"].as_str().unwrap())
+            .collect();
+        // Tied on activity, so in name order.
+        assert_eq!(subreddits, ["go", "rust"]);
+        assert_eq!(
+            profile["flair_history"][1],
+            serde_json::json!({
+                "subreddit": "rust", "flair": "Rustacean", "first_seen": FRIDAY + 8 * 3600,
+                "last_seen": FRIDAY + 8 * 3600 + 60, "comment_count": 2,
+            })
+        );
+        assert_eq!(profile["hours"][8], 2);
+        assert_eq!(profile["hours"][23], 1);
+        assert_eq!(profile["hours"][0], 1);
+        assert_eq!(
+            profile["weekdays"],
+            serde_json::json!({"Mon": 0, "Tue": 0, "Wed": 0, "Thu": 1, "Fri": 2, "Sat": 1, "Sun": 0})
+        );
+    }
+
+    #[test]
+    fn top_subreddits() {
+        let profile = profile(&["alice", "--top", "1"]);
+        assert_eq!(profile["subreddits"].as_array().unwrap().len(), 1);
+        assert_eq!(profile["more_subreddits"], 1);
+    }
+
+    #[test]
+    fn bars_scale_to_the_largest_bucket() {
+        let mut out = String::new();
+        histogram(
+            &mut out,
+            &["a".to_string(), "b".to_string(), "c".to_string()],
+            &[4, 1, 0],
+        );
+        let bars: Vec<usize> = out.lines().map(|line| line.matches('#').count()).collect();
+        assert_eq!(bars, [40, 10, 0]);
+    }
+}
@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use super::export;
+use crate::{
+    flair_history::DELETED,
+    output::{self, graph, Column, Kind},
+    timestamp,
+    value::Value,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("reply-graph")
+        .about("Build the author to author reply graph and write it as an edge list, GraphML or GEXF")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .required(true)
+                .takes_value(true)
+                .help("File to write: .csv or .tsv edge list, .graphml or .gexf, optionally with .gz, .bz2, .xz or .zst"),
+        )
+        .arg(
+            Arg::with_name("subreddit")
+                .long("subreddit")
+                .multiple(true)
+                .takes_value(true)
+                .help("Only use replies in these subreddits"),
+        )
+        .arg(
+            Arg::with_name("after")
+                .long("after")
+                .takes_value(true)
+                .help("Only use replies made after this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("before")
+                .long("before")
+                .takes_value(true)
+                .help("Only use replies made before this time, unix seconds or YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::with_name("min-count")
+                .long("min-count")
+                .takes_value(true)
+                .default_value("1")
+                .help("Leave out edges with fewer replies than this"),
+        )
+        .arg(
+            Arg::with_name("comments-only")
+                .long("comments-only")
+                .help("Only count replies to comments, not top level comments replying to a submission"),
+        )
+}
+
+// Reply edges between authors, per subreddit. Deleted authors and replies to oneself are left
+// out, and comments imported more than once are counted once.
+const EDGES: &str = "
+WITH replies AS (
+    SELECT c.reddit_id AS reddit_id, c.author AS source, p.author AS target, c.subreddit AS subreddit, c.created_utc AS created_utc
+    FROM comment c JOIN comment p ON p.reddit_id = c.parent_id
+    WHERE NOT c.parent_is_post {conditions}
+    {posts}
+)
+SELECT source, target, subreddit, count(DISTINCT reddit_id) AS count, min(created_utc) AS first_seen, max(created_utc) AS last_seen
+FROM replies
+WHERE source != target AND source != $1 AND target != $1
+GROUP BY source, target, subreddit
+HAVING count(DISTINCT reddit_id) >= $2
+ORDER BY source, target, subreddit";
+
+const POST_REPLIES: &str = "
+    UNION ALL
+    SELECT c.reddit_id, c.author, p.author, c.subreddit, c.created_utc
+    FROM comment c JOIN posts p ON p.pid = c.parent_id
+    WHERE c.parent_is_post {conditions}";
+
+/// The edge query for the filters given on the command line, with its parameters.
+fn query(matches: &ArgMatches) -> Result<(String, Vec<Value>)> {
+    let min_count: i64 = matches.value_of("min-count").unwrap().parse()?;
+    let mut params = vec![Value::Text(DELETED.to_string()), Value::Integer(min_count)];
+    let mut conditions = String::new();
+    if let Some(subreddits) = matches.values_of("subreddit") {
+        let mut placeholders = Vec::new();
+        for subreddit in subreddits {
+            params.push(Value::Text(subreddit.to_string()));
+            placeholders.push(format!("${}", params.len()));
+        }
+        conditions.push_str(&format!(
+            " AND c.subreddit IN ({})",
+            placeholders.join(", ")
+        ));
+    }
+    if let Some(after) = matches.value_of("after") {
+        params.push(Value::Integer(timestamp::parse(after)?));
+        conditions.push_str(&format!(" AND c.created_utc > ${}", params.len()));
+    }
+    if let Some(before) = matches.value_of("before") {
+        params.push(Value::Integer(timestamp::parse(before)?));
+        conditions.push_str(&format!(" AND c.created_utc < ${}", params.len()));
+    }
+    let posts = if matches.is_present("comments-only") {
+        String::new()
+    } else {
+        POST_REPLIES.replace("{conditions}", &conditions)
+    };
+    let sql = EDGES
+        .replace("{conditions}", &conditions)
+        .replace("{posts}", &posts);
+    Ok((sql, params))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let (sql, params) = query(matches)?;
+
+    let columns = vec![
+        Column::new("source", Kind::Text),
+        Column::new("target", Kind::Text),
+        Column::new("subreddit", Kind::Text),
+        Column::new("count", Kind::Integer),
+        Column::new("first_seen", Kind::Integer),
+        Column::new("last_seen", Kind::Integer),
+    ];
+    let path = Path::new(matches.value_of("output").unwrap());
+    if graph::is_graph(path) {
+        let mut edges = Vec::new();
+        db.query_each(&sql, &params, |row| {
+            edges.push(row);
+            Ok(())
+        })?;
+        graph::write(path, &columns, &edges, Some("count"))?;
+        println!("Wrote {} edges to {}", edges.len(), path.display());
+    } else {
+        let mut writer = output::create(path, columns)?;
+        db.query_each(&sql, &params, |row| writer.write(&row))?;
+        let rows = writer.finish()?;
+        println!("Wrote {} edges to {}", rows, path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::Database, test_support};
+
+    fn edges(args: &[&str]) -> Vec<Vec<Value>> {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        db.insert_post(&test_support::post("post", "op", "test", 1, 1))
+            .unwrap();
+        for comment in &[
+            test_support::reply("a", "alice", "t3_post", 10),
+            test_support::reply("b", "bob", "t1_a", 20),
+            // Imported twice, counted once.
+            test_support::reply("b", "bob", "t1_a", 20),
+            test_support::reply("c", "bob", "t1_a", 30),
+            test_support::reply("d", "alice", "t1_a", 40),
+            test_support::reply("e", "[deleted]", "t1_a", 50),
+        ] {
+            db.insert_comment(comment).unwrap();
+        }
+        db.commit().unwrap();
+
+        let matches = subcommand()
+            .get_matches_from(["reply-graph", ":memory:", "out.csv"].iter().chain(args));
+        let (sql, params) = query(&matches).unwrap();
+        let mut edges = Vec::new();
+        db.query_each(&sql, &params, |row| {
+            edges.push(row);
+            Ok(())
+        })
+        .unwrap();
+        edges
+    }
+
+    fn edge(source: &str, target: &str, count: i64, first_seen: i64, last_seen: i64) -> Vec<Value> {
+        vec![
+            Value::Text(source.to_string()),
+            Value::Text(target.to_string()),
+            Value::Text("test".to_string()),
+            Value::Integer(count),
+            Value::Integer(first_seen),
+            Value::Integer(last_seen),
+        ]
+    }
+
+    #[test]
+    fn counts_each_reply_once() {
+        assert_eq!(
+            edges(&[]),
+            [
+                edge("alice", "op", 1, 10, 10),
+                edge("bob", "alice", 2, 20, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_edges() {
+        assert_eq!(
+            edges(&["--comments-only"]),
+            [edge("bob", "alice", 2, 20, 30)]
+        );
+        assert_eq!(
+            edges(&["--min-count", "2"]),
+            [edge("bob", "alice", 2, 20, 30)]
+        );
+        assert_eq!(edges(&["--after", "25"]), [edge("bob", "alice", 1, 30, 30)]);
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use super::export::{self, filter_args, where_clause, Table};
+use crate::{
+    database::Database,
+    filter::Filter,
+    output::{json_object, Column, Kind},
+    timestamp,
+    value::Value,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    filter_args(
+        SubCommand::with_name("search")
+            .about("Full text search through comments or posts, best matches first"),
+    )
+    .arg(
+        Arg::with_name("query")
+            .required(true)
+            .takes_value(true)
+            .help("FTS5 query for Sqlite, websearch_to_tsquery syntax for Postgres"),
+    )
+    .arg(
+        Arg::with_name("sort")
+            .long("sort")
+            .takes_value(true)
+            .possible_values(&["relevance", "score", "new"])
+            .default_value("relevance")
+            .help("Order results by relevance (bm25 on Sqlite, ts_rank_cd on Postgres), score or time"),
+    )
+    .arg(
+        Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .default_value("20")
+            .help("Results per page"),
+    )
+    .arg(
+        Arg::with_name("page")
+            .long("page")
+            .takes_value(true)
+            .default_value("1")
+            .help("Page of results to show, starting at 1"),
+    )
+    .arg(
+        Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["table", "json"])
+            .default_value("table")
+            .help("Print a table, or one JSON object per result"),
+    )
+}
+
+/// The page of results asked for, with their columns, and the number of matches on all pages.
+fn search(db: &mut Database, matches: &ArgMatches) -> Result<(i64, Vec<Column>, Vec<Vec<Value>>)> {
+    let table = Table::by_name(matches.value_of("table").unwrap())?;
+    let limit: u64 = matches.value_of("limit").unwrap().parse()?;
+    let page: u64 = matches.value_of("page").unwrap().parse::<u64>()?.max(1);
+
+    let mut params = vec![Value::Text(matches.value_of("query").unwrap().to_string())];
+    let language = matches.value_of("fts-language").unwrap();
+    let search = db.text_search(table.name, 1, language)?;
+    let mut conditions = vec![search.condition];
+    Filter::from_matches(matches)?.add_conditions(table.name, &mut conditions, &mut params);
+    let conditions = where_clause(&conditions);
+
+    let mut total = 0;
+    db.query_each(
+        &format!("SELECT count(*) FROM {}{}", search.from, conditions),
+        &params,
+        |row| {
+            if let Some(Value::Integer(count)) = row.first() {
+                total = *count;
+            }
+            Ok(())
+        },
+    )?;
+
+    let (list, mut columns) = table.select(None)?;
+    columns.push(Column::new("relevance", Kind::Real));
+    columns.push(Column::new("snippet", Kind::Text));
+    let order = match matches.value_of("sort").unwrap() {
+        "score" => format!("{}.score DESC", table.name),
+        "new" => format!("{}.created_utc DESC", table.name),
+        _ => "relevance DESC".to_string(),
+    };
+    let sql = format!(
+        "SELECT {}, {} AS relevance, {} AS snippet FROM {}{} ORDER BY {}, {}.created_utc DESC LIMIT {} OFFSET {}",
+        list,
+        search.rank,
+        search.snippet,
+        search.from,
+        conditions,
+        order,
+        table.name,
+        limit,
+        (page - 1) * limit
+    );
+    let mut results = Vec::new();
+    db.query_each(&sql, &params, |row| {
+        results.push(row);
+        Ok(())
+    })?;
+    Ok((total, columns, results))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let (total, columns, results) = search(&mut db, matches)?;
+    let limit: u64 = matches.value_of("limit").unwrap().parse()?;
+    let page: u64 = matches.value_of("page").unwrap().parse::<u64>()?.max(1);
+
+    if matches.value_of("format") == Some("json") {
+        for row in &results {
+            println!("{}", json_object(&columns, row));
+        }
+        return Ok(());
+    }
+    print_table(&columns, &results);
+    let first = (page - 1) * limit;
+    if total == 0 {
+        println!("No matches");
+    } else if results.is_empty() {
+        println!("No results on page {}, there are {} match(es)", page, total);
+    } else {
+        println!(
+            "Results {}-{} of {}, page {} of {}",
+            first + 1,
+            first + results.len() as u64,
+            total,
+            page,
+            (total as u64).div_ceil(limit.max(1))
+        );
+    }
+    Ok(())
+}
+
+fn print_table(columns: &[Column], results: &[Vec<Value>]) {
+    let position = |name: &str| columns.iter().position(|column| column.name == name);
+    let text = |row: &[Value], name: &str| match position(name).map(|i| &row[i]) {
+        Some(Value::Text(s)) => s.clone(),
+        Some(Value::Integer(i)) => i.to_string(),
+        Some(Value::Real(f)) => format!("{:.2}", f),
+        _ => String::new(),
+    };
+    println!(
+        "{:>6}  {:<10} {:<20} {:<20} {:>6}  {:<10}  snippet",
+        "rel", "id", "subreddit", "author", "score", "date"
+    );
+    for row in results {
+        let date = match position("created_utc").map(|i| &row[i]) {
+            Some(Value::Integer(seconds)) => timestamp::date(*seconds),
+            _ => String::new(),
+        };
+        let snippet = text(row, "snippet")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "{:>6}  {:<10} {:<20} {:<20} {:>6}  {:<10}  {}",
+            text(row, "relevance"),
+            text(row, "id"),
+            text(row, "subreddit"),
+            text(row, "author"),
+            text(row, "score"),
+            date,
+            snippet
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    // Ids and snippets of the results, and the number of matches.
+    fn results(args: &[&str]) -> (i64, Vec<(String, String)>) {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        let comments = [
+            ("a", "rust", "snoo snoo snoo", 1),
+            (
+                "b",
+                "rust",
+                "a long comment that mentions snoo once among many other words",
+                30,
+            ),
+            ("c", "go", "snoo in another subreddit", 20),
+            ("d", "rust", "nothing to see", 100),
+        ];
+        for (i, (id, subreddit, body, score)) in comments.iter().enumerate() {
+            let mut comment = test_support::comment(id, "alice", subreddit, *score, i as i64);
+            comment.body = body.to_string();
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+        let matches =
+            subcommand().get_matches_from(["search", ":memory:", "snoo"].iter().chain(args));
+        let (total, columns, rows) = search(&mut db, &matches).unwrap();
+        let position = |name: &str| {
+            columns
+                .iter()
+                .position(|column| column.name == name)
+                .unwrap()
+        };
+        let text = |value: &Value| match value {
+            Value::Text(s) => s.clone(),
+            _ => String::new(),
+        };
+        let rows = rows
+            .iter()
+            .map(|row| (text(&row[position("id")]), text(&row[position("snippet")])))
+            .collect();
+        (total, rows)
+    }
+
+    fn ids(results: &[(String, String)]) -> Vec<&str> {
+        results.iter().map(|(id, _)| id.as_str()).collect()
+    }
+
+    #[test]
+    fn ranked_with_snippets() {
+        let (total, results) = results(&[]);
+        assert_eq!(total, 3);
+        assert_eq!(ids(&results), ["a", "c", "b"]);
+        assert_eq!(results[0].1, "**snoo** **snoo** **snoo**");
+    }
+
+    #[test]
+    fn sorted_filtered_and_paged() {
+        assert_eq!(ids(&results(&["--sort", "score"]).1), ["b", "c", "a"]);
+        assert_eq!(ids(&results(&["--sort", "new"]).1), ["c", "b", "a"]);
+        let (total, results) = results(&["--subreddit", "rust", "--limit", "1", "--page", "2"]);
+        assert_eq!(total, 2);
+        assert_eq!(ids(&results), ["b"]);
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::export::{self, where_clause, Table};
+use crate::{
+    database::Database,
+    filter::Filter,
+    output::{json_object, Kind},
+    timestamp,
+    value::Value,
+};
+
+// Results per request when `size` is not given, and the most a request can ask for.
+const DEFAULT_SIZE: u64 = 25;
+const MAX_SIZE: u64 = 1000;
+
+/// Comments shaped like Pushshift's. Parent ids are stored without their `t1`/`t3` type prefix,
+/// which is put back from `parent_is_post`.
+const COMMENTS: Table = Table {
+    name: "comment",
+    columns: &[
+        ("id", "comment.reddit_id", Kind::Text),
+        ("author", "comment.author", Kind::Text),
+        ("subreddit", "comment.subreddit", Kind::Text),
+        ("body", "comment.body", Kind::Text),
+        ("score", "comment.score", Kind::Integer),
+        ("created_utc", "comment.created_utc", Kind::Integer),
+        ("retrieved_on", "comment.retrieved_on", Kind::Integer),
+        (
+            "parent_id",
+            "CASE WHEN comment.parent_is_post THEN 't3_' ELSE 't1_' END || comment.parent_id",
+            Kind::Text,
+        ),
+        ("author_flair_text", "comment.author_flair_text", Kind::Text),
+        (
+            "controversiality",
+            "comment.controversiality",
+            Kind::Integer,
+        ),
+        ("edited", "comment.edited", Kind::Integer),
+    ],
+};
+
+const SUBMISSIONS: Table = Table {
+    name: "posts",
+    columns: &[
+        ("id", "posts.pid", Kind::Text),
+        ("author", "posts.author", Kind::Text),
+        ("subreddit", "posts.subreddit", Kind::Text),
+        ("title", "posts.title", Kind::Text),
+        ("selftext", "posts.selftext", Kind::Text),
+        ("url", "posts.url", Kind::Text),
+        (
+            "permalink",
+            "'/r/' || posts.subreddit || '/comments/' || posts.pid || '/'",
+            Kind::Text,
+        ),
+        ("score", "posts.score", Kind::Integer),
+        ("created_utc", "posts.created_utc", Kind::Integer),
+        ("retrieved_on", "posts.retrieved_on", Kind::Integer),
+        ("author_flair_text", "posts.author_flair_text", Kind::Text),
+        ("edited", "posts.edited", Kind::Integer),
+    ],
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("serve")
+        .about(
+            "Serve the Pushshift search API (/reddit/search/comment and /reddit/search/submission) \
+             from the database",
+        )
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("host")
+                .long("host")
+                .takes_value(true)
+                .default_value("127.0.0.1")
+                .help("Address to listen on"),
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .takes_value(true)
+                .default_value("8080")
+                .help("Port to listen on"),
+        )
+        .arg(
+            Arg::with_name("fts-language")
+                .long("fts-language")
+                .takes_value(true)
+                .default_value("english")
+                .help("Postgres text search configuration `q` is parsed with"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let language = matches.value_of("fts-language").unwrap();
+    let address = format!(
+        "{}:{}",
+        matches.value_of("host").unwrap(),
+        matches.value_of("port").unwrap()
+    );
+    let server =
+        Server::http(&address).map_err(|err| anyhow!("Can't listen on {}: {}", address, err))?;
+    println!("Listening on http://{}/reddit/search/comment", address);
+    // Requests are answered one at a time, over the one database connection.
+    for request in server.incoming_requests() {
+        let started = Instant::now();
+        let (status, body) = match route(&mut db, &request, language) {
+            Ok(body) => (200, body),
+            Err(Status(status, message)) => {
+                let error = serde_json::json!({ "error": message });
+                (status, error.to_string())
+            }
+        };
+        println!(
+            "{} {} {} {:.0?}",
+            request.method(),
+            request.url(),
+            status,
+            started.elapsed()
+        );
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json"))
+            .with_header(header("Access-Control-Allow-Origin", "*"));
+        if let Err(err) = request.respond(response) {
+            eprintln!("Error sending response: {}", err);
+        }
+    }
+    Ok(())
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+/// An error response: HTTP status and message.
+struct Status(u16, String);
+
+fn route(db: &mut Database, request: &Request, language: &str) -> Result<String, Status> {
+    if request.method() != &Method::Get {
+        return Err(Status(405, "Only GET is supported".to_string()));
+    }
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (request.url(), ""),
+    };
+    let table = match path.trim_end_matches('/') {
+        "/reddit/search/comment" | "/reddit/comment/search" => &COMMENTS,
+        "/reddit/search/submission" | "/reddit/submission/search" => &SUBMISSIONS,
+        _ => return Err(Status(404, format!("No endpoint at {}", path))),
+    };
+    search(db, table, query, language).map_err(|err| Status(400, format!("{:#}", err)))
+}
+
+/// Runs a search with Pushshift's parameters and returns the `{"data": [...]}` response.
+fn search(db: &mut Database, table: &Table, query: &str, language: &str) -> Result<String> {
+    let mut args: HashMap<String, String> = HashMap::new();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        // Repeated parameters add to the list, like a comma separated one.
+        args.entry(key.into_owned())
+            .and_modify(|existing| {
+                existing.push(',');
+                existing.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    let arg = |name: &str| args.get(name).map(String::as_str).filter(|v| !v.is_empty());
+    let list = |name: &str| -> Vec<String> {
+        arg(name)
+            .map(|v| v.split(',').map(|s| s.trim().to_string()).collect())
+            .unwrap_or_default()
+    };
+
+    let fields = arg("fields").map(|fields| fields.split(',').map(str::trim).collect());
+    let (select, columns) = table.select(fields)?;
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    let mut from = table.name.to_string();
+    if let Some(q) = arg("q") {
+        params.push(Value::Text(q.to_string()));
+        let search = db.text_search(table.name, params.len(), language)?;
+        from = search.from;
+        conditions.push(search.condition);
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    // Pushshift requires every given filter to match, so subreddits and authors are filtered
+    // separately rather than either one passing.
+    Filter {
+        subreddits: list("subreddit").into_iter().collect(),
+        after: arg("after").map(|t| time(t, now)).transpose()?,
+        before: arg("before").map(|t| time(t, now)).transpose()?,
+        ..Filter::default()
+    }
+    .add_conditions(table.name, &mut conditions, &mut params);
+    Filter {
+        usernames: list("author").into_iter().collect(),
+        ..Filter::default()
+    }
+    .add_conditions(table.name, &mut conditions, &mut params);
+    let ids = list("ids");
+    if !ids.is_empty() {
+        let mut placeholders = Vec::new();
+        for id in ids {
+            let id = id.trim_start_matches("t1_").trim_start_matches("t3_");
+            params.push(Value::Text(id.to_string()));
+            placeholders.push(format!("${}", params.len()));
+        }
+        conditions.push(format!(
+            "{} IN ({})",
+            table.columns[0].1,
+            placeholders.join(", ")
+        ));
+    }
+
+    // Older clients send sort=asc|desc with sort_type, newer ones sort=<field> with order.
+    let (sort, order) = match arg("sort") {
+        Some(order @ "asc") | Some(order @ "desc") => (arg("sort_type"), Some(order)),
+        sort => (sort, arg("order")),
+    };
+    let sort = match sort.unwrap_or("created_utc") {
+        "created_utc" => "created_utc",
+        "score" => "score",
+        other => bail!("Can't sort by {}, use created_utc or score", other),
+    };
+    let order = match order.unwrap_or("desc") {
+        "asc" => "ASC",
+        "desc" => "DESC",
+        other => bail!("Unknown order {}, use asc or desc", other),
+    };
+    let size = match arg("size") {
+        Some(size) => size.parse::<u64>()?.min(MAX_SIZE),
+        None => DEFAULT_SIZE,
+    };
+    let sql = format!(
+        "SELECT {} FROM {}{} ORDER BY {}.{} {} LIMIT {}",
+        select,
+        from,
+        where_clause(&conditions),
+        table.name,
+        sort,
+        order,
+        size
+    );
+    let mut data = Vec::new();
+    db.query_each(&sql, &params, |row| {
+        data.push(json_object(&columns, &row));
+        Ok(())
+    })?;
+    Ok(format!("{{\"data\":[{}]}}", data.join(",")))
+}
+
+/// Pushshift times: unix seconds, relative ones like `30d`, `12h`, `15m` or `90s` before now, and
+/// the dates `timestamp::parse` takes.
+fn time(text: &str, now: i64) -> Result<i64> {
+    let unit = match text.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 3_600,
+        Some('d') => 86_400,
+        _ => return timestamp::parse(text),
+    };
+    match text[..text.len() - 1].parse::<i64>() {
+        Ok(count) => Ok(now - count * unit),
+        Err(_) => timestamp::parse(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn relative_and_absolute_times() {
+        let now = 1_600_000_000;
+        assert_eq!(time("90s", now).unwrap(), now - 90);
+        assert_eq!(time("15m", now).unwrap(), now - 900);
+        assert_eq!(time("12h", now).unwrap(), now - 12 * 3_600);
+        assert_eq!(time("30d", now).unwrap(), now - 30 * 86_400);
+        assert_eq!(time("1500000000", now).unwrap(), 1_500_000_000);
+        assert_eq!(time("2020-01-01", now).unwrap(), 1_577_836_800);
+        assert!(time("soon", now).is_err());
+    }
+
+    #[test]
+    fn comments_come_back_shaped_like_pushshift() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        for (id, parent_id, subreddit) in &[
+            ("a", "t3_post", "rust"),
+            ("b", "t1_a", "rust"),
+            ("c", "t1_a", "python"),
+        ] {
+            let mut comment = test_support::reply(id, "someone", parent_id, 1);
+            comment.subreddit = subreddit.to_string();
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+
+        let response = search(
+            &mut db,
+            &COMMENTS,
+            "subreddit=rust&fields=id,parent_id&sort=asc&sort_type=created_utc",
+            "english",
+        )
+        .unwrap();
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({"data": [
+                {"id": "a", "parent_id": "t3_post"},
+                {"id": "b", "parent_id": "t1_a"},
+            ]})
+        );
+        assert!(search(&mut db, &COMMENTS, "sort=body", "english").is_err());
+    }
+}
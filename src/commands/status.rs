@@ -0,0 +1,20 @@
+use anyhow::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::database::Database;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("status")
+        .about("Show the schema version and which migrations have been applied")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = Database::open(matches.value_of("database").unwrap())?;
+    db.print_migration_status()
+}
@@ -0,0 +1,474 @@
+use std::{cmp::Reverse, fmt::Write};
+
+use anyhow::{bail, Result};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use hashbrown::{HashMap, HashSet};
+use serde_json::json;
+
+use super::export;
+use crate::{database::Database, timestamp, value::Value};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("thread")
+        .about("Rebuild the comment tree of a submission and print it as JSON, text or Markdown")
+        .arg(
+            Arg::with_name("database")
+                .required(true)
+                .takes_value(true)
+                .help("Sqlite file or postgres:// URL"),
+        )
+        .arg(
+            Arg::with_name("id")
+                .required(true)
+                .takes_value(true)
+                .help("Submission id, with or without the t3_ prefix"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(&["score", "new", "old"])
+                .default_value("score")
+                .help("Order of replies at each level"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["text", "markdown", "json"])
+                .default_value("text")
+                .help("Indented text, Markdown with nested quotes, or nested JSON"),
+        )
+}
+
+const POST: &str = "SELECT pid, author, subreddit, title, selftext, url, score, created_utc \
+                    FROM posts WHERE pid = $1";
+
+// Every comment below the submission, found by following parent ids down from it. UNION rather
+// than UNION ALL stops at comments that were already reached.
+const COMMENTS: &str = "
+WITH RECURSIVE tree(reddit_id) AS (
+    SELECT reddit_id FROM comment WHERE parent_is_post AND parent_id = $1
+    UNION
+    SELECT c.reddit_id FROM comment c, tree t
+    WHERE NOT c.parent_is_post AND c.parent_id = t.reddit_id
+)
+SELECT reddit_id, author, body, score, created_utc, parent_id
+FROM comment WHERE reddit_id IN (SELECT reddit_id FROM tree)";
+
+struct Post {
+    id: String,
+    author: String,
+    subreddit: String,
+    title: String,
+    selftext: String,
+    url: String,
+    score: i64,
+    created_utc: i64,
+}
+
+struct Comment {
+    id: String,
+    author: String,
+    body: String,
+    score: i64,
+    created_utc: i64,
+    replies: Vec<Comment>,
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let mut db = export::open(matches.value_of("database").unwrap())?;
+    let id = matches.value_of("id").unwrap();
+    let id = id.strip_prefix("t3_").unwrap_or(id);
+    let params = [Value::Text(id.to_string())];
+
+    let mut post = None;
+    db.query_each(POST, &params, |row| {
+        post = Some(Post {
+            id: row[0].to_text(),
+            author: row[1].to_text(),
+            subreddit: row[2].to_text(),
+            title: row[3].to_text(),
+            selftext: row[4].to_text(),
+            url: row[5].to_text(),
+            score: row[6].as_i64().unwrap_or(0),
+            created_utc: row[7].as_i64().unwrap_or(0),
+        });
+        Ok(())
+    })?;
+
+    let mut children = replies(&mut db, id)?;
+    if post.is_none() && children.is_empty() {
+        bail!("No submission or comments found for {}", id);
+    }
+
+    let sort = matches.value_of("sort").unwrap();
+    let comments = build(id, &mut children, sort);
+    let output = match matches.value_of("format").unwrap() {
+        "json" => serde_json::to_string_pretty(&to_json(id, post.as_ref(), &comments))?,
+        "markdown" => render_markdown(id, post.as_ref(), &comments),
+        _ => render_text(id, post.as_ref(), &comments),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+/// The comments below a submission by the id of what they reply to. A comment imported twice is
+/// only used once.
+fn replies(db: &mut Database, id: &str) -> Result<HashMap<String, Vec<Comment>>> {
+    let mut seen = HashSet::new();
+    let mut children: HashMap<String, Vec<Comment>> = HashMap::new();
+    db.query_each(COMMENTS, &[Value::Text(id.to_string())], |row| {
+        let comment = Comment {
+            id: row[0].to_text(),
+            author: row[1].to_text(),
+            body: row[2].to_text(),
+            score: row[3].as_i64().unwrap_or(0),
+            created_utc: row[4].as_i64().unwrap_or(0),
+            replies: Vec::new(),
+        };
+        if seen.insert(comment.id.clone()) {
+            children.entry(row[5].to_text()).or_default().push(comment);
+        }
+        Ok(())
+    })?;
+    Ok(children)
+}
+
+/// Takes the replies to `parent` out of `children`, with their own replies attached, sorted.
+fn build(parent: &str, children: &mut HashMap<String, Vec<Comment>>, sort: &str) -> Vec<Comment> {
+    let mut comments = children.remove(parent).unwrap_or_default();
+    for comment in &mut comments {
+        comment.replies = build(&comment.id, children, sort);
+    }
+    match sort {
+        "new" => comments.sort_by_key(|c| Reverse(c.created_utc)),
+        "old" => comments.sort_by_key(|c| c.created_utc),
+        _ => comments.sort_by_key(|c| Reverse(c.score)),
+    }
+    comments
+}
+
+fn count(comments: &[Comment]) -> usize {
+    comments.iter().map(|c| 1 + count(&c.replies)).sum()
+}
+
+fn to_json(id: &str, post: Option<&Post>, comments: &[Comment]) -> serde_json::Value {
+    fn comment_json(comment: &Comment) -> serde_json::Value {
+        json!({
+            "id": comment.id,
+            "author": comment.author,
+            "body": comment.body,
+            "score": comment.score,
+            "created_utc": comment.created_utc,
+            "replies": comment.replies.iter().map(comment_json).collect::<Vec<_>>(),
+        })
+    }
+    let post = post.map(|post| {
+        json!({
+            "id": post.id,
+            "author": post.author,
+            "subreddit": post.subreddit,
+            "title": post.title,
+            "selftext": post.selftext,
+            "url": post.url,
+            "score": post.score,
+            "created_utc": post.created_utc,
+        })
+    });
+    json!({
+        "id": id,
+        "post": post,
+        "comment_count": count(comments),
+        "comments": comments.iter().map(comment_json).collect::<Vec<_>>(),
+    })
+}
+
+fn render_text(id: &str, post: Option<&Post>, comments: &[Comment]) -> String {
+    fn comment_text(out: &mut String, comment: &Comment, depth: usize) {
+        let indent = "    ".repeat(depth);
+        let _ = writeln!(
+            out,
+            "{}{} ({} points, {})",
+            indent,
+            comment.author,
+            comment.score,
+            timestamp::date(comment.created_utc)
+        );
+        for line in comment.body.lines() {
+            let _ = writeln!(out, "{}  {}", indent, line);
+        }
+        out.push('\n');
+        for reply in &comment.replies {
+            comment_text(out, reply, depth + 1);
+        }
+    }
+    let mut out = String::new();
+    match post {
+        Some(post) => {
+            let _ = writeln!(out, "{}", post.title);
+            let _ = writeln!(
+                out,
+                "{} in r/{}, {} points, {}",
+                post.author,
+                post.subreddit,
+                post.score,
+                timestamp::date(post.created_utc)
+            );
+            if !post.selftext.is_empty() {
+                let _ = writeln!(out, "\n{}", post.selftext);
+            } else if !post.url.is_empty() {
+                let _ = writeln!(out, "{}", post.url);
+            }
+        }
+        None => {
+            let _ = writeln!(out, "Submission {} (not in the database)", id);
+        }
+    }
+    let _ = writeln!(out, "{} comment(s)\n", count(comments));
+    for comment in comments {
+        comment_text(&mut out, comment, 0);
+    }
+    out.trim_end().to_string()
+}
+
+fn render_markdown(id: &str, post: Option<&Post>, comments: &[Comment]) -> String {
+    // Each level of replies is one more level of block quote.
+    fn comment_markdown(out: &mut String, comment: &Comment, depth: usize) {
+        let quote = "> ".repeat(depth + 1);
+        let _ = writeln!(
+            out,
+            "{}**u/{}** · {} points · {}",
+            quote,
+            comment.author,
+            comment.score,
+            timestamp::date(comment.created_utc)
+        );
+        let _ = writeln!(out, "{}", quote.trim_end());
+        for line in comment.body.lines() {
+            let _ = writeln!(out, "{}{}", quote, line);
+        }
+        out.push('\n');
+        for reply in &comment.replies {
+            comment_markdown(out, reply, depth + 1);
+        }
+    }
+    let mut out = String::new();
+    match post {
+        Some(post) => {
+            let _ = writeln!(out, "# {}\n", post.title);
+            let _ = writeln!(
+                out,
+                "*u/{} in r/{} · {} points · {}*\n",
+                post.author,
+                post.subreddit,
+                post.score,
+                timestamp::date(post.created_utc)
+            );
+            if !post.selftext.is_empty() {
+                let _ = writeln!(out, "{}\n", post.selftext);
+            } else if !post.url.is_empty() {
+                let _ = writeln!(out, "<{}>\n", post.url);
+            }
+        }
+        None => {
+            let _ = writeln!(out, "# Submission {}\n", id);
+        }
+    }
+    let _ = writeln!(out, "## {} comment(s)\n", count(comments));
+    for comment in comments {
+        comment_markdown(&mut out, comment, 0);
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn comment(id: &str, parent_id: &str, score: i32, created_utc: i64) -> crate::comment::Comment {
+        let mut comment = test_support::reply(id, "someone", parent_id, created_utc);
+        comment.body = id.to_string();
+        comment.score = score;
+        comment
+    }
+
+    fn ids(comments: &[Comment]) -> Vec<&str> {
+        comments.iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn rebuilds_the_tree_from_the_database() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        for comment in &[
+            comment("a", "t3_post", 1, 10),
+            comment("b", "t3_post", 5, 20),
+            comment("c", "t1_a", 2, 30),
+            comment("d", "t1_c", 3, 40),
+            // Imported twice, used once.
+            comment("d", "t1_c", 3, 40),
+            // Replies to a comment that was never imported, and to another submission.
+            comment("e", "t1_missing", 1, 50),
+            comment("f", "t3_other", 1, 60),
+        ] {
+            db.insert_comment(comment).unwrap();
+        }
+        db.commit().unwrap();
+
+        let mut children = replies(&mut db, "post").unwrap();
+        let tree = build("post", &mut children, "score");
+        assert_eq!(ids(&tree), ["b", "a"]);
+        assert_eq!(ids(&tree[1].replies), ["c"]);
+        assert_eq!(ids(&tree[1].replies[0].replies), ["d"]);
+        assert_eq!(count(&tree), 4);
+    }
+
+    #[test]
+    fn sorts_replies_at_every_level() {
+        let tree = |sort| {
+            let mut children = HashMap::new();
+            children.insert(
+                "post".to_string(),
+                vec![
+                    Comment {
+                        id: "old".into(),
+                        author: String::new(),
+                        body: String::new(),
+                        score: 1,
+                        created_utc: 1,
+                        replies: Vec::new(),
+                    },
+                    Comment {
+                        id: "new".into(),
+                        author: String::new(),
+                        body: String::new(),
+                        score: 2,
+                        created_utc: 2,
+                        replies: Vec::new(),
+                    },
+                ],
+            );
+            ids(&build("post", &mut children, sort))
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tree("score"), ["new", "old"]);
+        assert_eq!(tree("new"), ["new", "old"]);
+        assert_eq!(tree("old"), ["old", "new"]);
+    }
+
+    // b and a on the submission, c replying to a, and e replying to a comment that is missing.
+    fn small_tree() -> Vec<Comment> {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        for comment in &[
+            comment("a", "t3_post", 1, 10),
+            comment("b", "t3_post", 5, 20),
+            comment("c", "t1_a", 2, 30),
+            comment("e", "t1_missing", 9, 40),
+        ] {
+            db.insert_comment(comment).unwrap();
+        }
+        db.commit().unwrap();
+        let mut children = replies(&mut db, "post").unwrap();
+        build("post", &mut children, "score")
+    }
+
+    fn post() -> Post {
+        Post {
+            id: "post".to_string(),
+            author: "alice".to_string(),
+            subreddit: "test".to_string(),
+            title: "Title".to_string(),
+            selftext: String::new(),
+            url: "https://example.com".to_string(),
+            score: 7,
+            created_utc: 0,
+        }
+    }
+
+    #[test]
+    fn text_indents_replies() {
+        assert_eq!(
+            render_text("post", Some(&post()), &small_tree()),
+            "Title
+alice in r/test, 7 points, 1970-01-01
+https://example.com
+3 comment(s)
+
+someone (5 points, 1970-01-01)
+  b
+
+someone (1 points, 1970-01-01)
+  a
+
+    someone (2 points, 1970-01-01)
+      c"
+        );
+        assert!(render_text("post", None, &[])
+            .starts_with("Submission post (not in the database)\n0 comment(s)"));
+    }
+
+    #[test]
+    fn markdown_nests_quotes() {
+        assert_eq!(
+            render_markdown("post", Some(&post()), &small_tree()),
+            "# Title
+
+*u/alice in r/test · 7 points · 1970-01-01*
+
+<https://example.com>
+
+## 3 comment(s)
+
+> **u/someone** · 5 points · 1970-01-01
+>
+> b
+
+> **u/someone** · 1 points · 1970-01-01
+>
+> a
+
+> > **u/someone** · 2 points · 1970-01-01
+> >
+> > c"
+        );
+        assert_eq!(
+            render_markdown("post", None, &[]),
+            "# Submission post\n\n## 0 comment(s)"
+        );
+    }
+
+    #[test]
+    fn json_nests_replies() {
+        let json = to_json("post", None, &small_tree());
+        assert_eq!(json["post"], serde_json::Value::Null);
+        assert_eq!(json["comment_count"], 3);
+        let comments = json["comments"].as_array().unwrap();
+        assert_eq!(
+            comments
+                .iter()
+                .map(|c| c["id"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["b", "a"]
+        );
+        assert_eq!(
+            comments[1]["replies"][0],
+            json!({
+                "id": "c",
+                "author": "someone",
+                "body": "c",
+                "score": 2,
+                "created_utc": 30,
+                "replies": [],
+            })
+        );
+        assert_eq!(
+            to_json("post", Some(&post()), &[])["post"]["url"],
+            "https://example.com"
+        );
+    }
+}
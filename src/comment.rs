@@ -1,7 +1,10 @@
 use anyhow::Context;
 use serde::Deserialize;
 
+use crate::{flair::Flair, link::Link, mention::Mention};
+
 #[derive(Deserialize, Debug, Clone)]
+#[allow(dead_code)]
 pub struct Comment {
     pub author: String,
     pub body: String,
@@ -9,7 +12,7 @@ pub struct Comment {
     #[serde(default)]
     pub author_flair_text: Option<String>,
     #[serde(default)]
-    author_flair_css_class: Option<String>,
+    pub author_flair_css_class: Option<String>,
     pub score: i32,
     ups: Option<i32>,
     downs: Option<i32>,
@@ -22,13 +25,22 @@ pub struct Comment {
     #[serde(default)]
     pub parent_is_post: bool,
     #[serde(default)]
-    stickied: bool,
+    pub stickied: bool,
     #[serde(default)]
-    distinguished: Option<String>,
+    pub distinguished: Option<String>,
     //    edited: Option<Edited>,
     #[serde(default)]
     archived: bool,
-    controversiality: Option<i32>,
+    pub controversiality: Option<i32>,
+    /// Mapped `author_flair_text`, filled in from the flair mapping during the import.
+    #[serde(skip)]
+    pub flair: Flair,
+    /// Links in `body`, filled in during database imports.
+    #[serde(skip)]
+    pub links: Vec<Link>,
+    /// Users and subreddits mentioned in `body`, filled in during database imports.
+    #[serde(skip)]
+    pub mentions: Vec<Mention>,
 }
 
 impl Comment {
@@ -54,7 +66,24 @@ impl Comment {
         if comment.parent_id.starts_with("t3_") {
             comment.parent_is_post = true;
         }
-        comment.parent_id = comment.parent_id.split_off(2);
+        // "t1_abc" or "t3_abc" is stored as "abc", with parent_is_post telling the two apart.
+        comment.parent_id = comment.parent_id.split_off(3);
         comment
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support;
+
+    fn parent(parent_id: &str) -> (String, bool) {
+        let comment = test_support::reply("abc", "someone", parent_id, 1);
+        (comment.parent_id, comment.parent_is_post)
+    }
+
+    #[test]
+    fn parent_id_loses_its_type_prefix() {
+        assert_eq!(parent("t1_xyz"), ("xyz".to_string(), false));
+        assert_eq!(parent("t3_post"), ("post".to_string(), true));
+    }
+}
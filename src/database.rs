@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    backfill::{Backfill, UpdateCounts},
+    comment::Comment,
+    flair_history::FlairChange,
+    migrations::{self, Migrator},
+    output::Column,
+    post::Post,
+    postgres::Postgres,
+    sqlite::{fts, Sqlite},
+    value::Value,
+};
+
+/// The output database, picked from the target given on the command line: `postgres://` and
+/// `postgresql://` URLs connect to Postgres, anything else is treated as a Sqlite file path.
+pub enum Database {
+    Sqlite(Box<Sqlite>),
+    Postgres(Box<Postgres>),
+}
+
+impl Database {
+    pub fn open(target: &str) -> Result<Self> {
+        if target.starts_with("postgres://") || target.starts_with("postgresql://") {
+            Ok(Database::Postgres(Box::new(Postgres::new(target)?)))
+        } else {
+            Ok(Database::Sqlite(Box::new(Sqlite::new(Path::new(target))?)))
+        }
+    }
+
+    fn migrator(&mut self) -> &mut dyn Migrator {
+        match self {
+            Database::Sqlite(db) => db.as_mut(),
+            Database::Postgres(db) => db.as_mut(),
+        }
+    }
+
+    pub fn migrate(&mut self) -> Result<usize> {
+        migrations::migrate(self.migrator())
+    }
+
+    pub fn print_migration_status(&mut self) -> Result<()> {
+        migrations::print_status(self.migrator())
+    }
+
+    /// Number of migrations that have not been applied yet.
+    pub fn pending_migrations(&mut self) -> Result<usize> {
+        let migrator = self.migrator();
+        let current = migrations::current_version(migrator)?;
+        Ok(migrator
+            .migrations()
+            .iter()
+            .filter(|migration| migration.version > current)
+            .count())
+    }
+
+    pub fn insert_comment(&mut self, comment: &Comment) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.insert_comment(comment),
+            Database::Postgres(db) => db.insert_comment(comment),
+        }
+    }
+
+    pub fn update_comment_field_by_reddit_id(&mut self, comment: &Comment) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.update_comment_field_by_reddit_id(comment),
+            Database::Postgres(db) => db.update_comment_field_by_reddit_id(comment),
+        }
+    }
+
+    pub fn insert_post(&mut self, post: &Post) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.insert_post(post),
+            Database::Postgres(db) => db.insert_post(post),
+        }
+    }
+
+    /// Flushes any writes still buffered by the backend.
+    pub fn commit(&mut self) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.commit(),
+            Database::Postgres(db) => {
+                db.flush_rows()?;
+                db.flush_backfill()?;
+                db.flush_flair_history()
+            }
+        }
+    }
+
+    /// Sets up a backfill of existing rows, see `stage_backfill`.
+    pub fn start_backfill(&mut self, backfill: Backfill) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.start_backfill(backfill),
+            Database::Postgres(db) => db.start_backfill(backfill),
+        }
+    }
+
+    /// Queues new column values for the row with reddit id `key`. They are applied in batches,
+    /// the last one by `commit`.
+    pub fn stage_backfill(&mut self, key: String, values: Vec<Value>) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.stage_backfill(key, values),
+            Database::Postgres(db) => db.stage_backfill(key, values),
+        }
+    }
+
+    /// How many staged updates found their row so far.
+    pub fn update_counts(&self) -> UpdateCounts {
+        match self {
+            Database::Sqlite(db) => db.update_counts(),
+            Database::Postgres(db) => db.update_counts(),
+        }
+    }
+
+    /// Recomputes `author_flair_history` from the comment table. Returns the number of rows.
+    pub fn rebuild_flair_history(&mut self) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.rebuild_flair_history(),
+            Database::Postgres(db) => db.rebuild_flair_history(),
+        }
+    }
+
+    /// Flair history of authors that used more than one flair in a subreddit, optionally only
+    /// in `subreddit`.
+    pub fn flair_changes(&mut self, subreddit: Option<&str>) -> Result<Vec<FlairChange>> {
+        match self {
+            Database::Sqlite(db) => db.flair_changes(subreddit),
+            Database::Postgres(db) => db.flair_changes(subreddit),
+        }
+    }
+
+    /// Runs a query and hands each row to `f`. `sql` uses `$1` style placeholders, which are
+    /// rewritten for Sqlite.
+    pub fn query_each(
+        &mut self,
+        sql: &str,
+        params: &[Value],
+        mut f: impl FnMut(Vec<Value>) -> Result<()>,
+    ) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.query_each(sql, params, &mut f),
+            Database::Postgres(db) => db.query_each(sql, params, &mut f),
+        }
+    }
+
+    /// Runs a statement with `$1` style placeholders, returning the number of rows changed.
+    pub fn execute(&mut self, sql: &str, params: &[Value]) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.execute(sql, params),
+            Database::Postgres(db) => db.execute(sql, params),
+        }
+    }
+
+    /// Runs each statement once for each of its parameter lists, all in one transaction.
+    /// Returns the number of rows changed.
+    pub fn execute_batch(&mut self, statements: &[(&str, Vec<Vec<Value>>)]) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.execute_batch(statements),
+            Database::Postgres(db) => db.execute_batch(statements),
+        }
+    }
+
+    /// Replaces table `name` with an empty one with the given columns.
+    pub fn create_table(&mut self, name: &str, columns: &[Column]) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.create_table(name, columns),
+            Database::Postgres(db) => db.create_table(name, columns),
+        }
+    }
+
+    /// Fails early if the full text search language is unknown to the backend. Sqlite's FTS5
+    /// tables do not use one.
+    pub fn check_fts_language(&mut self, language: &str) -> Result<()> {
+        match self {
+            Database::Sqlite(_) => Ok(()),
+            Database::Postgres(db) => db.text_search_config(language).map(|_| ()),
+        }
+    }
+
+    /// How to match rows of `table` against the full text query in parameter `param`: FTS5
+    /// query syntax for Sqlite, `websearch_to_tsquery` syntax in `language` for Postgres.
+    pub fn text_search(&mut self, table: &str, param: usize, language: &str) -> Result<TextSearch> {
+        match self {
+            Database::Sqlite(_) => match fts::for_content(table) {
+                Some(fts) => Ok(fts.search(param)),
+                None => bail!("{} has no full text index", table),
+            },
+            Database::Postgres(db) => db.text_search(table, param, language),
+        }
+    }
+
+    /// Builds the comment full text index after an import. Sqlite keeps `comment_fts` up to date
+    /// through triggers, so there is nothing to do there.
+    pub fn build_comment_fts(&mut self, language: &str) -> Result<()> {
+        match self {
+            Database::Sqlite(_) => Ok(()),
+            Database::Postgres(db) => db.build_comment_fts(language),
+        }
+    }
+
+    /// Drops the secondary indexes of `table` (and with `fts` its full text index) so a bulk
+    /// import does not have to maintain them row by row. Returns how many were dropped.
+    pub fn defer_indexes(&mut self, table: &str, fts: bool) -> Result<usize> {
+        match self {
+            Database::Sqlite(db) => db.defer_indexes(table, fts),
+            Database::Postgres(db) => db.defer_indexes(table, fts),
+        }
+    }
+
+    /// Recreates indexes dropped by `defer_indexes`. Safe to call when nothing was deferred.
+    pub fn restore_indexes(&mut self) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.restore_indexes(),
+            Database::Postgres(db) => db.restore_indexes(),
+        }
+    }
+
+    /// Refreshes planner statistics and reclaims space after a bulk import.
+    pub fn analyze(&mut self, table: &str) -> Result<()> {
+        match self {
+            Database::Sqlite(db) => db.analyze(),
+            Database::Postgres(db) => db.analyze(table),
+        }
+    }
+
+    pub fn build_post_fts(&mut self, language: &str) -> Result<()> {
+        match self {
+            Database::Sqlite(_) => Ok(()),
+            Database::Postgres(db) => db.build_post_fts(language),
+        }
+    }
+}
+
+/// SQL fragments for a full text query, in terms of the searched table's name.
+#[derive(Debug, Clone)]
+pub struct TextSearch {
+    /// Replaces the table in the `FROM` clause.
+    pub from: String,
+    /// Goes in the `WHERE` clause.
+    pub condition: String,
+    /// Relevance of a row, higher is better.
+    pub rank: String,
+    /// Matching part of the text, with the matched words between `**`.
+    pub snippet: String,
+}
+
+/// Turns a `CREATE INDEX` or `CREATE TRIGGER` statement read back from the schema into one that
+/// does nothing if the object already exists.
+pub fn if_not_exists(sql: &str) -> String {
+    if sql.contains(" IF NOT EXISTS ") {
+        return sql.to_string();
+    }
+    for kind in &[" INDEX ", " TRIGGER "] {
+        if let Some(position) = sql.find(kind) {
+            let end = position + kind.len();
+            return format!("{}IF NOT EXISTS {}", &sql[..end], &sql[end..]);
+        }
+    }
+    sql.to_string()
+}
@@ -0,0 +1,255 @@
+//! The username, subreddit and time range filters. The import checks records against them as
+//! they are read, and `export` and `search` turn them into SQL conditions, so all of them take
+//! the same options.
+
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use clap::{App, Arg, ArgMatches};
+
+use crate::{comment::Comment, post::Post, timestamp, value::Value};
+
+/// Adds `--username`, `--subreddit`, `--after` and `--before`.
+pub fn args(app: App<'static, 'static>) -> App<'static, 'static> {
+    app.arg(
+        Arg::with_name("username")
+            .long("username")
+            .multiple(true)
+            .takes_value(true)
+            .help("Add a username to the username filter"),
+    )
+    .arg(
+        Arg::with_name("subreddit")
+            .long("subreddit")
+            .multiple(true)
+            .takes_value(true)
+            .help("Add a subreddit to the subreddit filter"),
+    )
+    .arg(
+        Arg::with_name("after")
+            .long("after")
+            .takes_value(true)
+            .help("Only records created after this time, unix seconds or YYYY-MM-DD"),
+    )
+    .arg(
+        Arg::with_name("before")
+            .long("before")
+            .takes_value(true)
+            .help("Only records created before this time, unix seconds or YYYY-MM-DD"),
+    )
+}
+
+/// A record passes the username and subreddit filters if it matches either of them, or if
+/// neither is given. The time range, exclusive at both ends, must always match.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub usernames: BTreeSet<String>,
+    pub subreddits: BTreeSet<String>,
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+}
+
+impl Filter {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let values = |arg| {
+            matches
+                .values_of(arg)
+                .map(|values| values.map(str::to_string).collect())
+                .unwrap_or_default()
+        };
+        let time = |arg| -> Result<Option<i64>> {
+            matches
+                .value_of(arg)
+                .map(|time| timestamp::parse(time).with_context(|| format!("--{}", arg)))
+                .transpose()
+        };
+        Ok(Filter {
+            usernames: values("username"),
+            subreddits: values("subreddit"),
+            after: time("after")?,
+            before: time("before")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.usernames.is_empty()
+            && self.subreddits.is_empty()
+            && self.after.is_none()
+            && self.before.is_none()
+    }
+
+    pub fn comment(&self, comment: &Comment) -> bool {
+        self.matches(
+            &comment.author,
+            &comment.subreddit,
+            Some(comment.created_utc.into()),
+        )
+    }
+
+    pub fn post(&self, post: &Post) -> bool {
+        self.matches(
+            post.author.as_deref().unwrap_or(""),
+            post.subreddit.as_deref().unwrap_or(""),
+            Some(post.created_utc.into()),
+        )
+    }
+
+    /// For a dump line that was only parsed as JSON. `created_utc` may be a number or a string
+    /// of one.
+    pub fn json(&self, json: &serde_json::Value) -> bool {
+        let author = json.get("author").and_then(|a| a.as_str()).unwrap_or("");
+        let subreddit = json.get("subreddit").and_then(|s| s.as_str()).unwrap_or("");
+        let created_utc = json.get("created_utc").and_then(|created| match created {
+            serde_json::Value::String(s) => s.parse().ok(),
+            created => created
+                .as_i64()
+                .or_else(|| created.as_f64().map(|f| f as i64)),
+        });
+        self.matches(author, subreddit, created_utc)
+    }
+
+    /// Records without a creation time can't be placed in a time range, so they only pass
+    /// when none is given.
+    pub fn matches(&self, author: &str, subreddit: &str, created_utc: Option<i64>) -> bool {
+        let in_range = match created_utc {
+            Some(created_utc) => {
+                self.after.map_or(true, |after| created_utc > after)
+                    && self.before.map_or(true, |before| created_utc < before)
+            }
+            None => self.after.is_none() && self.before.is_none(),
+        };
+        if !in_range {
+            return false;
+        }
+        (self.usernames.is_empty() && self.subreddits.is_empty())
+            || self.usernames.contains(author)
+            || self.subreddits.contains(subreddit)
+    }
+
+    /// Adds the conditions for this filter on `table` to `conditions` and their parameters to
+    /// `params`.
+    pub fn add_conditions(
+        &self,
+        table: &str,
+        conditions: &mut Vec<String>,
+        params: &mut Vec<Value>,
+    ) {
+        let mut any_of = Vec::new();
+        for (values, column) in &[(&self.usernames, "author"), (&self.subreddits, "subreddit")] {
+            if values.is_empty() {
+                continue;
+            }
+            let mut placeholders = Vec::new();
+            for value in values.iter() {
+                params.push(Value::Text(value.clone()));
+                placeholders.push(format!("${}", params.len()));
+            }
+            any_of.push(format!(
+                "{}.{} IN ({})",
+                table,
+                column,
+                placeholders.join(", ")
+            ));
+        }
+        if !any_of.is_empty() {
+            conditions.push(format!("({})", any_of.join(" OR ")));
+        }
+        if let Some(after) = self.after {
+            params.push(Value::Integer(after));
+            conditions.push(format!("{}.created_utc > ${}", table, params.len()));
+        }
+        if let Some(before) = self.before {
+            params.push(Value::Integer(before));
+            conditions.push(format!("{}.created_utc < ${}", table, params.len()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::SubCommand;
+
+    fn filter(argv: &[&str]) -> Filter {
+        let matches = args(SubCommand::with_name("test"))
+            .get_matches_from(std::iter::once(&"test").chain(argv));
+        Filter::from_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn users_or_subreddits() {
+        let f = filter(&["--username", "alice", "--subreddit", "DotA2"]);
+        assert!(f.matches("alice", "rust", Some(1)));
+        assert!(f.matches("bob", "DotA2", Some(1)));
+        assert!(!f.matches("bob", "rust", Some(1)));
+        assert!(filter(&[]).matches("bob", "rust", None));
+    }
+
+    #[test]
+    fn time_range_always_applies() {
+        let f = filter(&["--subreddit", "DotA2", "--after", "100", "--before", "200"]);
+        assert!(f.matches("bob", "DotA2", Some(150)));
+        assert!(!f.matches("bob", "DotA2", Some(100)));
+        assert!(!f.matches("bob", "DotA2", Some(200)));
+        assert!(!f.matches("bob", "rust", Some(150)));
+        assert!(!f.matches("bob", "DotA2", None));
+        assert!(filter(&["--before", "200"]).matches("bob", "rust", Some(5)));
+        assert_eq!(filter(&["--after", "2020-01-01"]).after, Some(1577836800));
+    }
+
+    #[test]
+    fn invalid_time() {
+        let matches =
+            args(SubCommand::with_name("test")).get_matches_from(["test", "--before", "soon"]);
+        let err = Filter::from_matches(&matches).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("--before: "));
+    }
+
+    #[test]
+    fn dump_lines() {
+        let f = filter(&["--after", "100"]);
+        let line = |created_utc: serde_json::Value| serde_json::json!({"author": "bob", "subreddit": "rust", "created_utc": created_utc});
+        assert!(f.json(&line(serde_json::json!(150))));
+        assert!(f.json(&line(serde_json::json!("150"))));
+        assert!(f.json(&line(serde_json::json!(150.0))));
+        assert!(!f.json(&line(serde_json::json!(50))));
+        assert!(!f.json(&serde_json::json!({"author": "bob"})));
+    }
+
+    #[test]
+    fn sql_conditions() {
+        let f = filter(&[
+            "--username",
+            "bob",
+            "--username",
+            "alice",
+            "--subreddit",
+            "rust",
+            "--after",
+            "100",
+        ]);
+        let mut conditions = vec!["comment_fts MATCH $1".to_string()];
+        let mut params = vec![Value::Text("snoo".to_string())];
+        f.add_conditions("comment", &mut conditions, &mut params);
+        assert_eq!(
+            conditions,
+            [
+                "comment_fts MATCH $1",
+                "(comment.author IN ($2, $3) OR comment.subreddit IN ($4))",
+                "comment.created_utc > $5",
+            ]
+        );
+        assert_eq!(
+            params[1..],
+            [
+                Value::Text("alice".to_string()),
+                Value::Text("bob".to_string()),
+                Value::Text("rust".to_string()),
+                Value::Integer(100),
+            ]
+        );
+        let mut conditions = Vec::new();
+        filter(&[]).add_conditions("posts", &mut conditions, &mut Vec::new());
+        assert!(conditions.is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+use std::{
+    collections::HashMap as ConfigMap,
+    fs,
+    path::Path,
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{bail, Context, Result};
+use hashbrown::HashMap;
+use regex::Regex;
+use serde::Deserialize;
+
+const DEFAULT_MAPPING: &str = include_str!("flair.toml");
+
+/// The mapped code and label for a comment's or post's flair.
+#[derive(Debug, Clone, Default)]
+pub struct Flair {
+    pub code: Option<i32>,
+    pub label: Option<Arc<str>>,
+}
+
+impl Flair {
+    /// Label for the flair table: the one from the matching rule, or else the flair text without
+    /// its `:emoji:` codes and separators, e.g. `LibLeft` for `:libleft: - LibLeft`.
+    pub fn label(&self, text: &str) -> Option<String> {
+        if let Some(label) = &self.label {
+            return Some(label.to_string());
+        }
+        let label = EMOJI.replace_all(text, "");
+        let label = label.trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '|');
+        if label.is_empty() {
+            None
+        } else {
+            Some(label.to_string())
+        }
+    }
+}
+
+/// Key identifying a row of the flair table, `None` for rows without flair text.
+pub fn flair_key(subreddit: &str, text: Option<&str>, css_class: Option<&str>) -> Option<String> {
+    match text {
+        None | Some("") => None,
+        Some(text) => Some(format!(
+            "{}\0{}\0{}",
+            subreddit,
+            text,
+            css_class.unwrap_or("")
+        )),
+    }
+}
+
+static EMOJI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r":[^:\s]+:").unwrap());
+
+// How many unmapped flairs `print_summary` lists.
+const SUMMARY_SIZE: usize = 25;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    code: i32,
+    label: Option<String>,
+    exact: Option<String>,
+    prefix: Option<String>,
+    regex: Option<String>,
+}
+
+enum Matcher {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+pub struct Rule {
+    pub code: i32,
+    pub label: Option<Arc<str>>,
+    matcher: Matcher,
+}
+
+impl Rule {
+    fn matches(&self, flair: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(exact) => flair == exact,
+            Matcher::Prefix(prefix) => flair.starts_with(prefix.as_str()),
+            Matcher::Regex(regex) => regex.is_match(flair),
+        }
+    }
+}
+
+/// Turns `author_flair_text` into the integer codes stored in the `flair` columns, using
+/// per-subreddit rules. Flairs no rule matches are counted in an `UnmappedFlairs` so they can be
+/// reported at the end of an import.
+pub struct FlairMapper {
+    // Keyed by lowercase subreddit name.
+    rules: HashMap<String, Vec<Rule>>,
+}
+
+/// Flairs that no rule matched. Each import worker counts its own, and they are merged for
+/// `print_summary` once the workers are done.
+#[derive(Debug, Default)]
+pub struct UnmappedFlairs {
+    unmapped: HashMap<(String, String), usize>,
+    // Flaired rows per subreddit that has no rules at all.
+    unconfigured: HashMap<String, usize>,
+}
+
+impl FlairMapper {
+    /// The built in PoliticalCompassMemes mapping.
+    pub fn default_mapping() -> Self {
+        Self::from_toml(DEFAULT_MAPPING).expect("invalid built in flair mapping")
+    }
+
+    /// Loads a mapping from a `.json` file, or TOML for any other extension.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read flair mapping {}", path.display()))?;
+        let mapping = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&text).map_err(anyhow::Error::from)
+        };
+        let mapping =
+            mapping.with_context(|| format!("Failed to parse flair mapping {}", path.display()))?;
+        Self::new(mapping).with_context(|| format!("Invalid flair mapping {}", path.display()))
+    }
+
+    fn from_toml(text: &str) -> Result<Self> {
+        Self::new(toml::from_str(text)?)
+    }
+
+    fn new(config: ConfigMap<String, Vec<RuleConfig>>) -> Result<Self> {
+        let mut rules = HashMap::new();
+        for (subreddit, configs) in config {
+            let subreddit_rules = configs
+                .into_iter()
+                .map(|config| {
+                    let matcher = match (config.exact, config.prefix, config.regex) {
+                        (Some(exact), None, None) => Matcher::Exact(exact),
+                        (None, Some(prefix), None) => Matcher::Prefix(prefix),
+                        (None, None, Some(regex)) => Matcher::Regex(
+                            Regex::new(&regex)
+                                .with_context(|| format!("Bad regex for {}", subreddit))?,
+                        ),
+                        _ => bail!(
+                            "Rule for code {} in {} needs exactly one of exact, prefix or regex",
+                            config.code,
+                            subreddit
+                        ),
+                    };
+                    Ok(Rule {
+                        code: config.code,
+                        label: config.label.map(Arc::from),
+                        matcher,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            rules.insert(subreddit.to_lowercase(), subreddit_rules);
+        }
+        Ok(FlairMapper { rules })
+    }
+
+    /// The rule matching `flair` in `subreddit`, if any. Flairs without one are added to
+    /// `unmapped`.
+    pub fn rule(
+        &self,
+        subreddit: &str,
+        flair: &str,
+        unmapped: &mut UnmappedFlairs,
+    ) -> Option<&Rule> {
+        let rules = match self.rules.get(&subreddit.to_lowercase()) {
+            Some(rules) => rules,
+            None => {
+                *unmapped
+                    .unconfigured
+                    .entry(subreddit.to_string())
+                    .or_insert(0) += 1;
+                return None;
+            }
+        };
+        let rule = rules.iter().find(|rule| rule.matches(flair));
+        if rule.is_none() {
+            *unmapped
+                .unmapped
+                .entry((subreddit.to_string(), flair.to_string()))
+                .or_insert(0) += 1;
+        }
+        rule
+    }
+
+    /// What to store for a flair: code 0 when there is no flair, no code (NULL) when there is
+    /// one but no rule covers it.
+    pub fn map(
+        &self,
+        subreddit: &str,
+        flair: Option<&str>,
+        unmapped: &mut UnmappedFlairs,
+    ) -> Flair {
+        match flair {
+            None | Some("") => Flair {
+                code: Some(0),
+                label: None,
+            },
+            Some(flair) => match self.rule(subreddit, flair, unmapped) {
+                Some(rule) => Flair {
+                    code: Some(rule.code),
+                    label: rule.label.clone(),
+                },
+                None => Flair {
+                    code: None,
+                    label: None,
+                },
+            },
+        }
+    }
+}
+
+impl UnmappedFlairs {
+    /// Adds the counts of another worker.
+    pub fn merge(&mut self, other: UnmappedFlairs) {
+        for (key, count) in other.unmapped {
+            *self.unmapped.entry(key).or_insert(0) += count;
+        }
+        for (subreddit, count) in other.unconfigured {
+            *self.unconfigured.entry(subreddit).or_insert(0) += count;
+        }
+    }
+
+    /// Lists the most common flairs that had no matching rule.
+    pub fn print_summary(&self) {
+        if !self.unconfigured.is_empty() {
+            println!(
+                "{} flairs in {} subreddits without flair rules, stored as NULL",
+                self.unconfigured.values().sum::<usize>(),
+                self.unconfigured.len()
+            );
+        }
+        if self.unmapped.is_empty() {
+            return;
+        }
+        let total: usize = self.unmapped.values().sum();
+        let mut counts: Vec<_> = self.unmapped.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        println!(
+            "{} flairs matched no rule ({} distinct), stored as NULL:",
+            total,
+            counts.len()
+        );
+        for ((subreddit, flair), count) in counts.iter().take(SUMMARY_SIZE) {
+            println!("  {:>10}  {}  {:?}", count, subreddit, flair);
+        }
+        if counts.len() > SUMMARY_SIZE {
+            println!("  ... and {} more", counts.len() - SUMMARY_SIZE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    const RULES: &str = r#"
+[[Test]]
+exact = "Blue"
+code = 1
+label = "Blue team"
+
+[[Test]]
+prefix = ":red:"
+code = 2
+
+[[Test]]
+regex = "^(?i)green( \\d+)?$"
+code = 3
+"#;
+
+    fn code(mapper: &FlairMapper, subreddit: &str, flair: Option<&str>) -> Option<i32> {
+        mapper
+            .map(subreddit, flair, &mut UnmappedFlairs::default())
+            .code
+    }
+
+    #[test]
+    fn exact_prefix_and_regex_rules() {
+        let mapper = FlairMapper::from_toml(RULES).unwrap();
+        assert_eq!(code(&mapper, "Test", Some("Blue")), Some(1));
+        assert_eq!(code(&mapper, "Test", Some("Blue ")), None);
+        assert_eq!(code(&mapper, "Test", Some(":red: Red")), Some(2));
+        assert_eq!(code(&mapper, "Test", Some("Red :red:")), None);
+        assert_eq!(code(&mapper, "Test", Some("GREEN 7")), Some(3));
+        assert_eq!(code(&mapper, "Test", Some("greenish")), None);
+        // Subreddit names are matched without regard to case.
+        assert_eq!(code(&mapper, "test", Some("Blue")), Some(1));
+    }
+
+    #[test]
+    fn rule_labels() {
+        let mapper = FlairMapper::from_toml(RULES).unwrap();
+        let mut unmapped = UnmappedFlairs::default();
+        let flair = mapper.map("Test", Some("Blue"), &mut unmapped);
+        assert_eq!(flair.label("Blue").as_deref(), Some("Blue team"));
+        // Without a rule label, the flair text without emoji codes is used.
+        let flair = mapper.map("Test", Some(":red: - Red"), &mut unmapped);
+        assert_eq!(flair.label(":red: - Red").as_deref(), Some("Red"));
+        assert_eq!(Flair::default().label(":red: | ").as_deref(), None);
+    }
+
+    #[test]
+    fn no_flair_is_zero_and_unmapped_flair_is_null() {
+        let mapper = FlairMapper::from_toml(RULES).unwrap();
+        assert_eq!(code(&mapper, "Test", None), Some(0));
+        assert_eq!(code(&mapper, "Test", Some("")), Some(0));
+        assert_eq!(code(&mapper, "Test", Some("Purple")), None);
+        assert_eq!(code(&mapper, "Other", Some("Blue")), None);
+    }
+
+    #[test]
+    fn built_in_mapping() {
+        let mapper = FlairMapper::default_mapping();
+        let mut unmapped = UnmappedFlairs::default();
+        let flair = mapper.map(
+            "PoliticalCompassMemes",
+            Some(":libleft: - LibLeft"),
+            &mut unmapped,
+        );
+        assert_eq!(flair.code, Some(1));
+        assert_eq!(flair.label.as_deref(), Some("LibLeft"));
+        let flair = mapper.map(
+            "PoliticalCompassMemes",
+            Some(":authright: - AuthRight"),
+            &mut unmapped,
+        );
+        assert_eq!(flair.code, Some(5));
+        assert!(unmapped.unmapped.is_empty() && unmapped.unconfigured.is_empty());
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let both = "[[Test]]\nexact = \"a\"\nprefix = \"b\"\ncode = 1\n";
+        assert!(FlairMapper::from_toml(both).is_err());
+        let neither = "[[Test]]\ncode = 1\n";
+        assert!(FlairMapper::from_toml(neither).is_err());
+        let bad_regex = "[[Test]]\nregex = \"(\"\ncode = 1\n";
+        assert!(FlairMapper::from_toml(bad_regex).is_err());
+        let unknown_field = "[[Test]]\nexact = \"a\"\ncode = 1\ncolour = \"red\"\n";
+        assert!(FlairMapper::from_toml(unknown_field).is_err());
+    }
+
+    #[test]
+    fn json_mapping_file() {
+        let dir = TempDir::new("flair");
+        let path = dir.path().join("flair.json");
+        fs::write(&path, r#"{"Test": [{"exact": "Blue", "code": 4}]}"#).unwrap();
+        let mapper = FlairMapper::from_file(&path);
+        assert_eq!(code(&mapper.unwrap(), "Test", Some("Blue")), Some(4));
+    }
+
+    #[test]
+    fn unmapped_counts_merge() {
+        let mapper = FlairMapper::from_toml(RULES).unwrap();
+        let mut first = UnmappedFlairs::default();
+        let mut second = UnmappedFlairs::default();
+        mapper.map("Test", Some("Purple"), &mut first);
+        mapper.map("Test", Some("Purple"), &mut second);
+        mapper.map("Other", Some("Blue"), &mut second);
+        mapper.map("Test", Some("Blue"), &mut second);
+        first.merge(second);
+        assert_eq!(
+            first
+                .unmapped
+                .get(&("Test".to_string(), "Purple".to_string())),
+            Some(&2)
+        );
+        assert_eq!(first.unconfigured.get("Other"), Some(&1));
+        assert_eq!(first.unmapped.len() + first.unconfigured.len(), 2);
+    }
+
+    #[test]
+    fn flair_keys() {
+        assert_eq!(flair_key("Test", None, None), None);
+        assert_eq!(flair_key("Test", Some(""), Some("x")), None);
+        assert_eq!(
+            flair_key("Test", Some("Blue"), None),
+            flair_key("Test", Some("Blue"), Some(""))
+        );
+        assert_ne!(
+            flair_key("Test", Some("Blue"), Some("a")),
+            flair_key("Test", Some("Blue"), Some("b"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Which flairs each author has used in each subreddit, and when.
+//!
+//! Imports collect spans in memory and the backends merge them into `author_flair_history`
+//! whenever they write a batch, so the table is kept up to date without a write per comment.
+
+use std::hash::Hash;
+
+use hashbrown::HashMap;
+
+/// Comments by deleted accounts all share this author, so they are left out of the history.
+pub const DELETED: &str = "[deleted]";
+
+/// When an author was first and last seen with one flair, and how many comments they made with it.
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub first_seen: i32,
+    pub last_seen: i32,
+    pub comment_count: i64,
+}
+
+/// Spans collected since the last flush, keyed by author, subreddit and flair id.
+pub struct FlairHistory<Id> {
+    spans: HashMap<(String, String, Id), Span>,
+}
+
+impl<Id: Hash + Eq> FlairHistory<Id> {
+    pub fn new() -> Self {
+        FlairHistory {
+            spans: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, author: &str, subreddit: &str, flair_id: Id, created_utc: i32) {
+        if author == DELETED {
+            return;
+        }
+        let span = self
+            .spans
+            .entry((author.to_string(), subreddit.to_string(), flair_id))
+            .or_insert(Span {
+                first_seen: created_utc,
+                last_seen: created_utc,
+                comment_count: 0,
+            });
+        span.first_seen = span.first_seen.min(created_utc);
+        span.last_seen = span.last_seen.max(created_utc);
+        span.comment_count += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.spans.clear();
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = ((String, String, Id), Span)> + '_ {
+        self.spans.drain()
+    }
+}
+
+/// One row of the multiple flair report.
+#[derive(Debug)]
+pub struct FlairChange {
+    pub author: String,
+    pub subreddit: String,
+    pub flair: String,
+    pub span: Span,
+}
+
+/// Prints authors that used more than one flair in a subreddit, oldest flair first. `changes`
+/// must be ordered by author and subreddit.
+pub fn print_changes(changes: &[FlairChange]) {
+    let mut authors = 0;
+    let mut previous: Option<(&str, &str)> = None;
+    for change in changes {
+        let key = (change.author.as_str(), change.subreddit.as_str());
+        if previous != Some(key) {
+            println!("{} in r/{}", change.author, change.subreddit);
+            previous = Some(key);
+            authors += 1;
+        }
+        println!(
+            "    {:<30} {:>8} comments  {} - {}",
+            change.flair, change.span.comment_count, change.span.first_seen, change.span.last_seen
+        );
+    }
+    println!("{} author(s) with more than one flair", authors);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::Database, test_support};
+
+    #[test]
+    fn spans_per_flair() {
+        let mut history = FlairHistory::new();
+        history.record("alice", "test", 1, 20);
+        history.record("alice", "test", 1, 10);
+        history.record("alice", "test", 1, 30);
+        history.record("alice", "test", 2, 40);
+        history.record(DELETED, "test", 1, 50);
+        assert_eq!(history.len(), 2);
+        let span = history.drain().find(|((_, _, id), _)| *id == 1).unwrap().1;
+        assert_eq!(
+            (span.first_seen, span.last_seen, span.comment_count),
+            (10, 30, 3)
+        );
+        assert!(history.is_empty());
+    }
+
+    fn changes(db: &mut Database) -> Vec<(String, String, i32, i32, i64)> {
+        db.flair_changes(Some("test"))
+            .unwrap()
+            .into_iter()
+            .map(|change| {
+                (
+                    change.author,
+                    change.flair,
+                    change.span.first_seen,
+                    change.span.last_seen,
+                    change.span.comment_count,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merged_across_batches_and_rebuilt() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.migrate().unwrap();
+        let comments = [
+            ("alice", "Blue", 10),
+            ("bob", "Blue", 15),
+            ("alice", "Red", 20),
+            ("alice", "Blue", 30),
+            (DELETED, "Red", 35),
+            ("bob", "Blue", 40),
+        ];
+        for (i, (author, flair, created_utc)) in comments.iter().enumerate() {
+            let mut comment =
+                test_support::comment(&format!("c{}", i), author, "test", 1, *created_utc);
+            comment.author_flair_text = Some(flair.to_string());
+            db.insert_comment(&comment).unwrap();
+            // Each comment in its own batch, so the spans are merged in the table.
+            db.commit().unwrap();
+        }
+        // bob only ever used one flair.
+        let expected = [
+            ("alice".to_string(), "Blue".to_string(), 10, 30, 2),
+            ("alice".to_string(), "Red".to_string(), 20, 20, 1),
+        ];
+        assert_eq!(changes(&mut db), expected);
+        assert_eq!(db.rebuild_flair_history().unwrap(), 3);
+        assert_eq!(changes(&mut db), expected);
+    }
+}
@@ -0,0 +1,299 @@
+//! Outbound links in comment bodies, post self text and post urls, for the `link` table.
+//!
+//! Markdown links (`[text](url)`), `<url>` autolinks and bare `http(s)://` or `www.` urls are
+//! found. Reddit-relative Markdown targets such as `/r/rust/wiki` or `u/spez` become
+//! `https://www.reddit.com/...` links.
+
+use std::sync::LazyLock;
+
+use hashbrown::HashSet;
+use regex::Regex;
+
+use crate::post::Post;
+
+// [text](target "title"), where the target may contain escaped characters and one level of
+// balanced parentheses, as Wikipedia urls often do.
+static MARKDOWN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\[[^\]\n]*\]\(\s*<?((?:\\.|[^()\s\\<>]|\([^()\s]*\))+)>?(?:\s+"[^"\n]*")?\s*\)"#)
+        .unwrap()
+});
+static AUTOLINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<((?:https?://|www\.)[^>\s]+)>").unwrap());
+static BARE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\b(?:https?://|www\.)[^\s<>\[\]"'`]+"#).unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub url: String,
+    /// Lowercase host without port, `www.` or `m.`, with reddit.com subdomains folded into it.
+    pub domain: String,
+}
+
+/// Links in Markdown text, each url once: Markdown links and autolinks first, then bare urls.
+pub fn extract(text: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut seen = HashSet::new();
+    let mut add = |link: Option<Link>| {
+        if let Some(link) = link {
+            if seen.insert(link.url.clone()) {
+                links.push(link);
+            }
+        }
+    };
+    // Text already taken by Markdown links and autolinks is blanked out, so the url in
+    // `[https://a.com](https://a.com)` isn't found again as a bare one.
+    let mut rest = text.to_string();
+    for regex in [&*MARKDOWN, &*AUTOLINK] {
+        let mut taken = Vec::new();
+        for captures in regex.captures_iter(&rest) {
+            add(target(&captures[1]));
+            taken.push(captures.get(0).unwrap().range());
+        }
+        for range in taken {
+            rest.replace_range(range.clone(), &" ".repeat(range.len()));
+        }
+    }
+    for found in BARE.find_iter(&rest) {
+        add(absolute(trim_end(found.as_str())));
+    }
+    links
+}
+
+/// Links of a submission: the url it points to, unless it is a self post pointing at its own
+/// comments, then the ones in its self text.
+pub fn post_links(post: &Post) -> Vec<Link> {
+    let mut links: Vec<Link> = Vec::new();
+    if !post.url.contains(&format!("/comments/{}", post.id)) {
+        links.extend(absolute(post.url.trim()));
+    }
+    for link in extract(&post.selftext) {
+        if !links.iter().any(|l| l.url == link.url) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// A Markdown link target as an absolute url.
+fn target(target: &str) -> Option<Link> {
+    let target = target.trim();
+    if target.starts_with("//") {
+        absolute(&format!("https:{}", target))
+    } else if target.starts_with('/') {
+        reddit(target)
+    } else if ["r/", "u/", "user/"].iter().any(|p| target.starts_with(p)) {
+        reddit(&format!("/{}", target))
+    } else {
+        absolute(target)
+    }
+}
+
+fn reddit(path: &str) -> Option<Link> {
+    absolute(&format!("https://www.reddit.com{}", path))
+}
+
+/// Unescapes a http(s) or `www.` url and works out its domain. Anything else is not a link.
+fn absolute(url: &str) -> Option<Link> {
+    let url = unescape(url);
+    let lowercase = url.to_ascii_lowercase();
+    let url = if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+        url
+    } else if lowercase.starts_with("www.") {
+        format!("http://{}", url)
+    } else {
+        return None;
+    };
+    let domain = domain(&url)?;
+    Some(Link { url, domain })
+}
+
+fn domain(url: &str) -> Option<String> {
+    let host = url.split("://").nth(1)?;
+    let host = host.split(['/', '?', '#']).next()?;
+    let host = host.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    let mut host = host.trim_end_matches('.').to_lowercase();
+    for prefix in &["www.", "m.", "mobile."] {
+        match host.strip_prefix(prefix) {
+            Some(stripped) if stripped.contains('.') => {
+                host = stripped.to_string();
+                break;
+            }
+            _ => {}
+        }
+    }
+    if host.ends_with(".reddit.com") {
+        host = "reddit.com".to_string();
+    }
+    if host.is_empty() || !host.contains('.') {
+        return None;
+    }
+    Some(host)
+}
+
+/// Drops Markdown backslash escapes and the `&amp;` the dumps have in place of `&`.
+fn unescape(url: &str) -> String {
+    let mut unescaped = String::with_capacity(url.len());
+    let mut chars = url.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped.replace("&amp;", "&")
+}
+
+/// A bare url without the punctuation that usually follows it in a sentence, keeping closing
+/// parentheses that have an opening one in the url.
+fn trim_end(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let last = match url.chars().last() {
+            Some(c) => c,
+            None => return url,
+        };
+        let unbalanced = last == ')' && url.matches('(').count() < url.matches(')').count();
+        if unbalanced || ".,;:!?*~'\"".contains(last) {
+            url = &url[..url.len() - last.len_utf8()];
+        } else {
+            return url;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn urls(text: &str) -> Vec<String> {
+        extract(text).into_iter().map(|link| link.url).collect()
+    }
+
+    #[test]
+    fn markdown_links_autolinks_and_bare_urls() {
+        assert_eq!(
+            urls(
+                r#"[docs](https://doc.rust-lang.org/book/ "The Book") <http://auto.net/p> and www.bare.io/page"#
+            ),
+            [
+                "https://doc.rust-lang.org/book/",
+                "http://auto.net/p",
+                "http://www.bare.io/page"
+            ]
+        );
+        // The url of a Markdown link isn't found again as a bare one.
+        assert_eq!(
+            urls("[https://a.com/x](https://a.com/x) https://a.com/x"),
+            ["https://a.com/x"]
+        );
+        assert!(urls("[mail](mailto:x@y.com) [anchor](#top) no links").is_empty());
+    }
+
+    #[test]
+    fn markdown_targets_with_parentheses() {
+        assert_eq!(
+            urls("see [Foo](https://en.wikipedia.org/wiki/Foo_(bar)) there"),
+            ["https://en.wikipedia.org/wiki/Foo_(bar)"]
+        );
+        assert_eq!(
+            urls(r"[escaped](https://example.com/a\_b\)c)"),
+            ["https://example.com/a_b)c"]
+        );
+    }
+
+    #[test]
+    fn trailing_punctuation_is_trimmed() {
+        assert_eq!(
+            urls("go to https://example.com/page."),
+            ["https://example.com/page"]
+        );
+        assert_eq!(urls("**https://bold.com/y**"), ["https://bold.com/y"]);
+        assert_eq!(
+            urls("(https://m.example.org/x)"),
+            ["https://m.example.org/x"]
+        );
+        assert_eq!(
+            urls("https://en.wikipedia.org/wiki/Foo_(bar), right?"),
+            ["https://en.wikipedia.org/wiki/Foo_(bar)"]
+        );
+        assert_eq!(
+            urls("https://youtube.com/watch?v=abc&amp;t=10s!"),
+            ["https://youtube.com/watch?v=abc&t=10s"]
+        );
+    }
+
+    #[test]
+    fn reddit_relative_links() {
+        assert_eq!(
+            extract("[faq](/r/rust/wiki/faq) [u](u/spez) [r](r/DotA2) [s](//old.reddit.com/r/a)"),
+            [
+                Link {
+                    url: "https://www.reddit.com/r/rust/wiki/faq".to_string(),
+                    domain: "reddit.com".to_string()
+                },
+                Link {
+                    url: "https://www.reddit.com/u/spez".to_string(),
+                    domain: "reddit.com".to_string()
+                },
+                Link {
+                    url: "https://www.reddit.com/r/DotA2".to_string(),
+                    domain: "reddit.com".to_string()
+                },
+                Link {
+                    url: "https://old.reddit.com/r/a".to_string(),
+                    domain: "reddit.com".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn domains_are_normalized() {
+        assert_eq!(
+            domain("https://www.YouTube.com/watch").unwrap(),
+            "youtube.com"
+        );
+        assert_eq!(
+            domain("http://user:pw@Host.Example.COM:8080/p").unwrap(),
+            "host.example.com"
+        );
+        assert_eq!(domain("https://m.example.org?q=1").unwrap(), "example.org");
+        assert_eq!(
+            domain("https://mobile.twitter.com/x").unwrap(),
+            "twitter.com"
+        );
+        assert_eq!(domain("https://np.reddit.com/r/a").unwrap(), "reddit.com");
+        assert_eq!(domain("http://example.com.:80").unwrap(), "example.com");
+        // Prefixes are kept when nothing with a dot would be left.
+        assert_eq!(domain("http://www.com/").unwrap(), "www.com");
+        assert_eq!(domain("http://localhost/"), None);
+    }
+
+    #[test]
+    fn post_links_skip_the_posts_own_comments() {
+        let post = |url: &str, selftext: &str| {
+            let mut post = test_support::post("abc", "alice", "test", 1, 1);
+            post.url = url.to_string();
+            post.selftext = selftext.to_string();
+            post
+        };
+        let urls =
+            |post: Post| -> Vec<String> { post_links(&post).into_iter().map(|l| l.url).collect() };
+        assert_eq!(
+            urls(post(
+                "https://www.reddit.com/r/test/comments/abc/title/",
+                "see https://a.com"
+            )),
+            ["https://a.com"]
+        );
+        assert_eq!(
+            urls(post(
+                "https://i.imgur.com/x.jpg",
+                "https://i.imgur.com/x.jpg"
+            )),
+            ["https://i.imgur.com/x.jpg"]
+        );
+    }
+}
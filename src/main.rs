@@ -4,10 +4,28 @@ extern crate hashbrown;
 extern crate serde;
 extern crate serde_json;
 
+mod aggregate;
+mod backfill;
+mod commands;
 mod comment;
+mod database;
 mod decompress;
+mod filter;
+mod flair;
+mod flair_history;
+mod link;
+mod mention;
+mod migrations;
+mod output;
 mod post;
+mod postgres;
+mod progress;
+mod sink;
 mod sqlite;
+#[cfg(test)]
+mod test_support;
+mod timestamp;
+mod value;
 
 use core::panic;
 use std::{
@@ -21,19 +39,24 @@ use std::{
     thread, time,
 };
 
-use crate::hashbrown::HashSet;
-use crate::sqlite::Sqlite;
+use crate::backfill::Backfill;
+use crate::database::Database;
+use crate::filter::Filter;
+use crate::flair::{FlairMapper, UnmappedFlairs};
+use crate::sink::Sink;
+use crate::value::Value;
 use bzip2::read::BzDecoder;
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg};
 use xz2::read::XzDecoder;
 
 use comment::Comment;
 use post::Post;
 
 fn main() {
-    let matches = App::new("pushshift-importer")
+    let app = App::new("pushshift-importer")
         .version("0.1")
         .author("Paul Ellenbogen")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("input-dir")
                 .required(true)
@@ -41,26 +64,45 @@ fn main() {
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("sqlite-outfile")
+            Arg::with_name("database")
                 .required(true)
-                .help("Path for for output Sqlite database.")
+                .help("Path for the output Sqlite database, a postgres:// URL, or where --sink writes to")
                 .takes_value(true),
+        );
+    let matches = filter::args(app)
+    .arg(
+            Arg::with_name("flair-mapping")
+                .long("flair-mapping")
+                .takes_value(true)
+                .help("TOML or JSON file mapping flair text to codes per subreddit, see src/flair.toml"),
         )
         .arg(
-            Arg::with_name("username")
-                .long("username")
-                .required(false)
-                .multiple(true)
+            Arg::with_name("fts-language")
+                .long("fts-language")
                 .takes_value(true)
-                .help("Add a username to the username filter"),
+                .default_value("english")
+                .help("Postgres text search configuration used for the full text index"),
         )
         .arg(
-            Arg::with_name("subreddit")
-                .long("subreddit")
-                .required(false)
-                .multiple(true)
-                .takes_value(true)
-                .help("Add a subreddit to the subreddit filter"),
+            Arg::with_name("skip-fts")
+                .long("skip-fts")
+                .help("Don't build the full text index after the import"),
+        )
+        .arg(
+            Arg::with_name("bulk")
+                .long("bulk")
+                .help("Drop secondary indexes and defer full text indexing while inserting, \
+                       then rebuild them and ANALYZE/VACUUM the database"),
+        )
+        .arg(
+            Arg::with_name("aggregate")
+                .long("aggregate")
+                .help("Update the daily and monthly subreddit rollups after inserting, see the aggregate subcommand"),
+        )
+        .arg(
+            Arg::with_name("no-migrate")
+                .long("no-migrate")
+                .help("Don't apply pending schema migrations before importing"),
         )
         .arg(
             Arg::with_name("operation")
@@ -70,166 +112,348 @@ fn main() {
                 .takes_value(true)
                 .help("please provide an operation"),
         )
-        .about("Import data from pushshift dump into a Sqlite database. Currently limited to comment data only.\
+        .arg(
+            Arg::with_name("fields")
+                .long("fields")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .help("Dump fields to copy onto existing rows with --operation backfill or backfill_post"),
+        )
+        .arg(
+            Arg::with_name("sink")
+                .long("sink")
+                .takes_value(true)
+                .possible_values(&["database", "parquet", "csv", "ndjson"])
+                .default_value("database")
+                .help("Write records to files instead of a database, with insert or insert_post"),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .help("Columns written by --sink csv, in order [default: all]"),
+        )
+        .arg(
+            Arg::with_name("no-header")
+                .long("no-header")
+                .help("Leave out the row of column names with --sink csv"),
+        )
+        .arg(
+            Arg::with_name("compression")
+                .long("compression")
+                .takes_value(true)
+                .help("Parquet compression: none, snappy, gzip or zstd, optionally with a level like zstd:9 [default: snappy]"),
+        )
+        .arg(
+            Arg::with_name("row-group-size")
+                .long("row-group-size")
+                .takes_value(true)
+                .help("Rows per Parquet row group [default: 100000]"),
+        )
+        .arg(
+            Arg::with_name("partition-by-subreddit")
+                .long("partition-by-subreddit")
+                .help("Split Parquet output by subreddit as well as by month"),
+        )
+        .about("Import data from pushshift dump into a Sqlite or Postgres database.\
         Multiple filters can be applied, and if any of the filter criteria match, the comment is included. If no filters are supplied, all comments match; ie the whole dataset will be added to the sqlite file.")
+        .subcommands(commands::subcommands())
         .get_matches();
-    let users: HashSet<String> = matches
-        .values_of("username")
-        .map(|users| users.map(|user| user.to_string()).collect())
-        .unwrap_or_else(HashSet::new);
-    let subreddits: HashSet<String> = matches
-        .values_of("subreddit")
-        .map(|users| users.map(|user| user.to_string()).collect())
-        .unwrap_or_else(HashSet::new);
-    let sqlite_filename = Path::new(matches.value_of("sqlite-outfile").unwrap());
-    let mut sqlite = Sqlite::new(sqlite_filename).expect("Error setting up sqlite DB");
-    let filter: CommentFilter = CommentFilter { users, subreddits };
+    if let Some(result) = commands::run(&matches) {
+        if let Err(err) = result {
+            eprintln!("{:#}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let filter = Filter::from_matches(&matches).unwrap_or_else(|err| {
+        eprintln!("{:#}", err);
+        std::process::exit(1);
+    });
+    let flair = match matches.value_of("flair-mapping") {
+        Some(path) => FlairMapper::from_file(Path::new(path)).expect("Error loading flair mapping"),
+        None => FlairMapper::default_mapping(),
+    };
+    let flair = Arc::new(flair);
     let input_dir = Path::new(matches.value_of("input-dir").unwrap());
     let file_list = get_file_list(input_dir);
-    process(
-        file_list,
-        filter,
-        &mut sqlite,
-        matches.value_of("operation").unwrap(),
-    );
+    let operation = matches.value_of("operation").unwrap();
+    let sink = matches.value_of("sink").unwrap();
+    if sink != "database" {
+        if operation != "insert" && operation != "insert_post" {
+            eprintln!("--sink {} only works with insert and insert_post", sink);
+            std::process::exit(1);
+        }
+        let target = Path::new(matches.value_of("database").unwrap());
+        let mut sink = sink::open(sink, target, &matches).expect("Error opening output");
+        let mut unmapped = UnmappedFlairs::default();
+        progress::timed("Loading", || {
+            unmapped = process(
+                file_list,
+                filter,
+                flair.clone(),
+                None,
+                sink.as_mut(),
+                operation,
+                false,
+            );
+            sink.finish()
+        })
+        .expect("Error writing output");
+        unmapped.print_summary();
+        return;
+    }
+    let mut db = Database::open(matches.value_of("database").unwrap())
+        .expect("Error opening output database");
+    if matches.is_present("no-migrate") {
+        let pending = db
+            .pending_migrations()
+            .expect("Error reading schema version");
+        if pending > 0 {
+            println!("Skipping {} pending migration(s)", pending);
+        }
+    } else {
+        db.migrate().expect("Error migrating database");
+    }
+    let language = matches.value_of("fts-language").unwrap();
+    db.check_fts_language(language)
+        .expect("Unknown text search configuration");
+    let build_fts = !matches.is_present("skip-fts");
+    let table = match operation {
+        "insert_post" | "backfill_post" => "posts",
+        _ => "comment",
+    };
+    let backfill = match operation {
+        "backfill" | "backfill_post" => {
+            let fields = matches.values_of("fields").into_iter().flatten();
+            let backfill = Backfill::from_fields(table, fields).expect("Error in --fields");
+            db.start_backfill(backfill.clone())
+                .expect("Error setting up backfill");
+            Some(Arc::new(backfill))
+        }
+        _ => None,
+    };
+    // Updates look rows up by reddit_id, so they need the indexes in place.
+    let bulk = matches.is_present("bulk") && operation != "update_flair" && backfill.is_none();
+    if bulk {
+        // Inserts also add to the link and mention tables, so their indexes wait as well.
+        progress::timed("Dropping secondary indexes", || {
+            Ok(db.defer_indexes(table, build_fts)?
+                + db.defer_indexes("link", false)?
+                + db.defer_indexes("mention", false)?)
+        })
+        .expect("Error dropping indexes");
+    }
+    let mut unmapped = UnmappedFlairs::default();
+    progress::timed("Loading", || {
+        unmapped = process(
+            file_list,
+            filter,
+            flair.clone(),
+            backfill.clone(),
+            &mut db,
+            operation,
+            true,
+        );
+        db.finish()
+    })
+    .expect("Error committing to database");
+    unmapped.print_summary();
+    if backfill.is_some() {
+        db.update_counts().print("Backfill");
+    }
+    if operation == "update_flair" {
+        db.update_counts().print("Flair update");
+        progress::timed("Rebuilding author flair history", || {
+            db.rebuild_flair_history()
+        })
+        .expect("Error rebuilding author flair history");
+    }
+    db.restore_indexes().expect("Error creating indexes");
+    if build_fts {
+        match operation {
+            "insert" => db.build_comment_fts(language),
+            "insert_post" => db.build_post_fts(language),
+            _ => Ok(()),
+        }
+        .expect("Error building full text index");
+    }
+    if bulk {
+        db.analyze(table).expect("Error analyzing database");
+    }
+    if matches.is_present("aggregate") && (operation == "insert" || operation == "insert_post") {
+        let summary = progress::timed("Updating subreddit rollups", || {
+            aggregate::update(&mut db, false, aggregate::TOP_AUTHORS)
+        })
+        .expect("Error updating subreddit rollups");
+        println!(
+            "Recomputed {} day(s) and month(s) of subreddit rollups",
+            summary.periods
+        );
+    }
 }
 
-fn process(file_list: Vec<PathBuf>, filter: CommentFilter, db: &mut Sqlite, ops: &str) {
+fn process(
+    file_list: Vec<PathBuf>,
+    filter: Filter,
+    flair: Arc<FlairMapper>,
+    backfill: Option<Arc<Backfill>>,
+    sink: &mut dyn Sink,
+    ops: &str,
+    extract: bool,
+) -> UnmappedFlairs {
     let shared_file_list = Arc::new(RwLock::new(file_list));
     let shared_filter = Arc::new(filter);
     let completed = Arc::new(AtomicUsize::new(0));
     let mut threads = Vec::new();
     let (txpost, rxpost) = mpsc::sync_channel(100000);
     let (tx, rx) = mpsc::sync_channel(100000);
-    let num_cpus = num_cpus::get_physical();
+    let (txbackfill, rxbackfill) = mpsc::sync_channel(100000);
+    let (txline, rxline) = mpsc::sync_channel(100000);
+    let num_workers = num_cpus::get_physical().saturating_sub(1).max(1);
 
-    let opCode = match ops {
+    let op_code = match ops {
         "insert" => 1,
         "update_flair" => 2,
         "insert_post" => 3,
+        "backfill" | "backfill_post" => 4,
         _ => 0,
     };
+    let op_code = if sink.raw_lines() && op_code != 0 {
+        5
+    } else {
+        op_code
+    };
+    // Only inserts write links and mentions, and the workers find them so the writer isn't slowed
+    // down.
+    let extract = extract && (op_code == 1 || op_code == 3);
 
-    for _i in 0..(num_cpus - 1) {
+    for _i in 0..num_workers {
         let filter_context = FilterContext::new(
             shared_filter.clone(),
+            flair.clone(),
             shared_file_list.clone(),
             completed.clone(),
             tx.clone(),
             txpost.clone(),
+            extract,
         );
-        let thread = thread::spawn(move || {
-            if opCode == 3 {
-                filter_context.process_queue_post();
-            } else {
-                filter_context.process_queue_comment();
+        let backfill = backfill.clone();
+        let txbackfill = txbackfill.clone();
+        let txline = txline.clone();
+        let thread = thread::spawn(move || match op_code {
+            3 => filter_context.process_queue_post(),
+            5 => {
+                filter_context.process_queue_lines(txline);
+                UnmappedFlairs::default()
             }
+            4 => {
+                filter_context.process_queue_backfill(backfill.as_deref().unwrap(), txbackfill);
+                UnmappedFlairs::default()
+            }
+            _ => filter_context.process_queue_comment(),
         });
         threads.push(thread);
     }
 
-    if opCode == 0 {
+    if op_code == 0 {
         panic!("couldn't figure out operation goodbye!")
     }
 
-    // let mut updatedCount = 0;
-    let mut slept = false;
-
     // time to read one type!
-    if opCode == 3 {
-        loop {
-            let maybe_post = rxpost.try_recv();
-            match maybe_post {
-                Ok(post) => {
-                    slept = true;
-                    let count = match opCode {
-                        3 => db.insert_post(&post).expect("Error inserting comment"),
-                        _ => panic!("invalid opCode"),
-                    };
-                    // updatedCount = updatedCount + count
-                }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    println!("Disconnect?");
-                    maybe_post.unwrap();
-                }
-                Err(mpsc) => {
-                    slept = true;
-                    if completed.load(Ordering::Relaxed) < (num_cpus - 1) {
-                        println!("Sleeping? {}", mpsc);
-                        thread::sleep(time::Duration::from_secs(1));
-                    } else {
-                        break;
-                    }
-                }
-            }
-        }
+    if op_code == 5 {
+        drain(rxline, &completed, num_workers, |line| {
+            sink.write_line(&line).expect("Error writing line")
+        });
+    } else if op_code == 4 {
+        drain(rxbackfill, &completed, num_workers, |(key, values)| {
+            sink.stage_backfill(key, values)
+                .expect("Error staging backfill")
+        });
+    } else if op_code == 3 {
+        drain(rxpost, &completed, num_workers, |post| {
+            sink.insert_post(&post).expect("Error inserting post")
+        });
+    } else if op_code == 2 {
+        drain(rx, &completed, num_workers, |comment| {
+            sink.update_flair(&comment)
+                .expect("Error while updating comment")
+        });
     } else {
-        loop {
-            let maybe_comment = rx.try_recv();
-            match maybe_comment {
-                Ok(comment) => {
-                    slept = true;
-                    let count = match opCode {
-                        1 => db
-                            .insert_comment(&comment)
-                            .expect("Error inserting comment"),
-                        2 => db
-                            .update_comment_field_by_reddit_id(&comment)
-                            .expect("Error while updating comment"),
-                        _ => panic!("invalid opCode"),
-                    };
-                }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    println!("Disconnect?");
-                    maybe_comment.unwrap();
-                }
-                Err(mpsc) => {
-                    slept = true;
-                    if completed.load(Ordering::Relaxed) < (num_cpus - 1) {
-                        println!("Sleeping? {}", mpsc);
-                        thread::sleep(time::Duration::from_secs(1));
-                    } else {
-                        break;
-                    }
+        drain(rx, &completed, num_workers, |comment| {
+            sink.insert_comment(&comment)
+                .expect("Error inserting comment")
+        });
+    }
+    let mut unmapped = UnmappedFlairs::default();
+    for thread in threads {
+        unmapped.merge(thread.join().unwrap());
+    }
+    unmapped
+}
+
+/// Hands everything the workers send over `rx` to `write`, until all `num_workers` of them are
+/// done.
+fn drain<T>(
+    rx: mpsc::Receiver<T>,
+    completed: &AtomicUsize,
+    num_workers: usize,
+    mut write: impl FnMut(T),
+) {
+    loop {
+        match rx.try_recv() {
+            Ok(item) => write(item),
+            Err(mpsc::TryRecvError::Disconnected) => break,
+            Err(mpsc::TryRecvError::Empty) => {
+                if completed.load(Ordering::Relaxed) < num_workers {
+                    thread::sleep(time::Duration::from_secs(1));
+                } else {
+                    break;
                 }
             }
         }
     }
-    for thread in threads {
-        thread.join().unwrap();
-    }
 }
 
 fn get_file_list(dir: &Path) -> Vec<PathBuf> {
     fs::read_dir(dir)
         .unwrap()
-        .into_iter()
         .filter_map(|dir_entry| dir_entry.ok().map(|ent| ent.path()))
         .collect()
 }
 
 struct FilterContext {
-    filter: Arc<CommentFilter>,
+    filter: Arc<Filter>,
+    flair: Arc<FlairMapper>,
     queue: Arc<RwLock<Vec<PathBuf>>>,
     completed: Arc<AtomicUsize>,
     send_channel_comment: mpsc::SyncSender<comment::Comment>,
     send_channel_post: mpsc::SyncSender<post::Post>,
+    // Fill in the links and mentions of comments and posts.
+    extract: bool,
 }
 
 impl FilterContext {
     fn new(
-        filter: Arc<CommentFilter>,
+        filter: Arc<Filter>,
+        flair: Arc<FlairMapper>,
         queue: Arc<RwLock<Vec<PathBuf>>>,
         completed: Arc<AtomicUsize>,
         send_channel_comment: mpsc::SyncSender<comment::Comment>,
         send_channel_post: mpsc::SyncSender<post::Post>,
+        extract: bool,
     ) -> Self {
         FilterContext {
             filter,
+            flair,
             queue,
             completed,
             send_channel_comment,
             send_channel_post,
+            extract,
         }
     }
 
@@ -238,55 +462,118 @@ impl FilterContext {
         queue.pop()
     }
 
-    fn process_queue_comment(&self) {
-        let mut readCount = 0;
+    /// Sends the comments that pass the filter, returning the flairs no rule matched.
+    fn process_queue_comment(&self) -> UnmappedFlairs {
+        let mut unmapped = UnmappedFlairs::default();
+        let mut read_count = 0;
         while let Some(filename) = self.get_next_file() {
-            for comment in iter_comments(filename.as_path()) {
-                if self.filter.filter(&comment) {
+            for mut comment in iter_comments(filename.as_path()) {
+                if self.filter.comment(&comment) {
+                    comment.flair = self.flair.map(
+                        &comment.subreddit,
+                        comment.author_flair_text.as_deref(),
+                        &mut unmapped,
+                    );
+                    if self.extract {
+                        comment.links = link::extract(&comment.body);
+                        comment.mentions = mention::extract(&comment.body);
+                    }
                     self.send_channel_comment.send(comment).unwrap();
                 }
-                readCount += 1;
-                if readCount % 1000 == 0 {
-                    println!("read: {}", readCount);
+                read_count += 1;
+                if read_count % 1000 == 0 {
+                    println!("read: {}", read_count);
                 }
             }
         }
         self.completed.fetch_add(1, Ordering::Relaxed);
+        unmapped
     }
 
-    fn process_queue_post(&self) {
-        let mut readCount = 0;
+    /// Sends the posts that pass the filter, returning the flairs no rule matched.
+    fn process_queue_post(&self) -> UnmappedFlairs {
+        let mut unmapped = UnmappedFlairs::default();
+        let mut read_count = 0;
         while let Some(filename) = self.get_next_file() {
-            for posts in iter_posts(filename.as_path()) {
-                self.send_channel_post.send(posts).unwrap();
-                readCount += 1;
-                if readCount % 1000 == 0 {
-                    println!("read: {}", readCount);
+            for mut post in iter_posts(filename.as_path()) {
+                if self.filter.post(&post) {
+                    if let Some(subreddit) = &post.subreddit {
+                        post.flair = self.flair.map(
+                            subreddit,
+                            post.author_flair_text.as_deref(),
+                            &mut unmapped,
+                        );
+                    }
+                    if self.extract {
+                        post.links = link::post_links(&post);
+                        post.mentions = mention::post_mentions(&post);
+                    }
+                    self.send_channel_post.send(post).unwrap();
+                }
+                read_count += 1;
+                if read_count % 1000 == 0 {
+                    println!("read: {}", read_count);
                 }
             }
         }
         self.completed.fetch_add(1, Ordering::Relaxed);
+        unmapped
     }
-}
 
-#[derive(Debug, Clone, Default)]
-struct CommentFilter {
-    users: HashSet<String>,
-    subreddits: HashSet<String>,
-}
-
-impl CommentFilter {
-    fn filter(&self, comment: &comment::Comment) -> bool {
-        if self.users.is_empty() && self.subreddits.is_empty() {
-            return true;
-        }
-        if self.users.contains(comment.author.as_str()) {
-            return true;
+    fn process_queue_backfill(
+        &self,
+        backfill: &Backfill,
+        send_channel: mpsc::SyncSender<(String, Vec<Value>)>,
+    ) {
+        let mut read_count = 0;
+        while let Some(filename) = self.get_next_file() {
+            for line in iter_lines(filename.as_path()) {
+                let line = line.unwrap();
+                let json: serde_json::Value = serde_json::from_str(&line).unwrap_or_else(|err| {
+                    panic!("Failed to read json for line: {}: {}", line, err)
+                });
+                if self.filter.json(&json) {
+                    if let Some(row) = backfill.row(&json) {
+                        send_channel.send(row).unwrap();
+                    }
+                }
+                read_count += 1;
+                if read_count % 1000 == 0 {
+                    println!("read: {}", read_count);
+                }
+            }
         }
-        if self.subreddits.contains(comment.subreddit.as_str()) {
-            return true;
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sends the lines that pass the filter unchanged. Lines are only parsed when there is a
+    /// filter to check.
+    fn process_queue_lines(&self, send_channel: mpsc::SyncSender<String>) {
+        let mut read_count = 0;
+        let filtered = !self.filter.is_empty();
+        while let Some(filename) = self.get_next_file() {
+            for line in iter_lines(filename.as_path()) {
+                let line = line.unwrap();
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let keep = !filtered || {
+                    let json: serde_json::Value =
+                        serde_json::from_str(&line).unwrap_or_else(|err| {
+                            panic!("Failed to read json for line: {}: {}", line, err)
+                        });
+                    self.filter.json(&json)
+                };
+                if keep {
+                    send_channel.send(line).unwrap();
+                }
+                read_count += 1;
+                if read_count % 1000 == 0 {
+                    println!("read: {}", read_count);
+                }
+            }
         }
-        false
+        self.completed.fetch_add(1, Ordering::Relaxed);
     }
 }
 
@@ -296,28 +583,7 @@ fn deserialize_lines(line: io::Result<String>) -> comment::Comment {
 }
 
 fn iter_comments(filename: &Path) -> Box<dyn Iterator<Item = comment::Comment>> {
-    let extension = filename.extension().unwrap().to_str().unwrap();
-    if extension == "gz" {
-        let gzip_file = decompress::gzip_file(filename);
-        let iter = gzip_file.lines().into_iter().map(deserialize_lines);
-        return Box::new(iter);
-    } else if extension == "bz2" {
-        let reader = fs::File::open(filename).unwrap();
-        let decoder = BufReader::new(BzDecoder::new(reader));
-        let iter = decoder.lines().into_iter().map(deserialize_lines);
-        return Box::new(iter);
-    } else if extension == "xz" {
-        let reader = fs::File::open(filename).unwrap();
-        let decoder = BufReader::new(XzDecoder::new_multi_decoder(reader));
-        let iter = decoder.lines().into_iter().map(deserialize_lines);
-        return Box::new(iter);
-    } else if extension == "zst" {
-        let reader = fs::File::open(filename).unwrap();
-        let decoder = BufReader::new(zstd::stream::read::Decoder::new(reader).unwrap());
-        let iter = decoder.lines().into_iter().map(deserialize_lines);
-        return Box::new(iter);
-    }
-    panic!("Unknown file extension for file {}", filename.display());
+    Box::new(iter_lines(filename).map(deserialize_lines))
 }
 
 fn deserialize_lines_posts(line: io::Result<String>) -> post::Post {
@@ -326,26 +592,27 @@ fn deserialize_lines_posts(line: io::Result<String>) -> post::Post {
 }
 
 fn iter_posts(filename: &Path) -> Box<dyn Iterator<Item = post::Post>> {
+    Box::new(iter_lines(filename).map(deserialize_lines_posts))
+}
+
+/// Lines of a dump file, decompressed according to its extension.
+fn iter_lines(filename: &Path) -> Box<dyn Iterator<Item = io::Result<String>>> {
     let extension = filename.extension().unwrap().to_str().unwrap();
     if extension == "gz" {
         let gzip_file = decompress::gzip_file(filename);
-        let iter = gzip_file.lines().into_iter().map(deserialize_lines_posts);
-        return Box::new(iter);
+        return Box::new(gzip_file.lines());
     } else if extension == "bz2" {
         let reader = fs::File::open(filename).unwrap();
         let decoder = BufReader::new(BzDecoder::new(reader));
-        let iter = decoder.lines().into_iter().map(deserialize_lines_posts);
-        return Box::new(iter);
+        return Box::new(decoder.lines());
     } else if extension == "xz" {
         let reader = fs::File::open(filename).unwrap();
         let decoder = BufReader::new(XzDecoder::new_multi_decoder(reader));
-        let iter = decoder.lines().into_iter().map(deserialize_lines_posts);
-        return Box::new(iter);
+        return Box::new(decoder.lines());
     } else if extension == "zst" {
         let reader = fs::File::open(filename).unwrap();
         let decoder = BufReader::new(zstd::stream::read::Decoder::new(reader).unwrap());
-        let iter = decoder.lines().into_iter().map(deserialize_lines_posts);
-        return Box::new(iter);
+        return Box::new(decoder.lines());
     }
     panic!("Unknown file extension for file {}", filename.display());
 }
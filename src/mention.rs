@@ -0,0 +1,153 @@
+//! User and subreddit mentions (`u/name`, `/u/name`, `/user/name`, `r/name`, `/r/name`) in
+//! comment bodies and post titles and self text, for the `mention` table.
+
+use std::sync::LazyLock;
+
+use hashbrown::HashSet;
+use regex::Regex;
+
+use crate::post::Post;
+
+// A mention starts the text or follows something other than a word character or `/`, so the
+// `/r/rust` in `reddit.com/r/rust` is part of a url rather than a mention.
+static MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^\w/])/?(u|user|r)/([a-z0-9_-]+)").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mention {
+    /// `user` or `subreddit`.
+    pub kind: &'static str,
+    /// Lowercase name of the user or subreddit, without the prefix.
+    pub target: String,
+}
+
+/// Mentions in Markdown text, each once.
+pub fn extract(text: &str) -> Vec<Mention> {
+    // The dumps escape underscores in names typed in the rich text editor.
+    let text = text.replace("\\_", "_");
+    let mut seen = HashSet::new();
+    let mut mentions = Vec::new();
+    for captures in MENTION.captures_iter(&text) {
+        let mention = match captures[1].to_ascii_lowercase().as_str() {
+            "r" => subreddit(&captures[2]),
+            _ => user(&captures[2]),
+        };
+        if let Some(mention) = mention {
+            if seen.insert((mention.kind, mention.target.clone())) {
+                mentions.push(mention);
+            }
+        }
+    }
+    mentions
+}
+
+/// Mentions in a submission's title and self text.
+pub fn post_mentions(post: &Post) -> Vec<Mention> {
+    extract(&format!("{}\n{}", post.title, post.selftext))
+}
+
+/// Usernames are 3 to 20 letters, digits, `_` or `-`.
+fn user(name: &str) -> Option<Mention> {
+    if !(3..=20).contains(&name.len()) {
+        return None;
+    }
+    Some(Mention {
+        kind: "user",
+        target: name.to_lowercase(),
+    })
+}
+
+/// Subreddit names are 2 to 21 letters, digits or `_`. Reddit ends the link at a `-`, so the
+/// name does too.
+fn subreddit(name: &str) -> Option<Mention> {
+    let name = name.split('-').next().unwrap_or("");
+    if !(2..=21).contains(&name.len()) {
+        return None;
+    }
+    Some(Mention {
+        kind: "subreddit",
+        target: name.to_lowercase(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn mentions(text: &str) -> Vec<(&'static str, String)> {
+        extract(text)
+            .into_iter()
+            .map(|mention| (mention.kind, mention.target))
+            .collect()
+    }
+
+    fn user(name: &str) -> (&'static str, String) {
+        ("user", name.to_string())
+    }
+
+    fn subreddit(name: &str) -> (&'static str, String) {
+        ("subreddit", name.to_string())
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(
+            mentions("u/alice, /u/bob; /user/carol (r/rust) and /r/DotA2"),
+            [
+                user("alice"),
+                user("bob"),
+                user("carol"),
+                subreddit("rust"),
+                subreddit("dota2")
+            ]
+        );
+        assert_eq!(mentions("user/dave"), [user("dave")]);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(
+            mentions("U/Alice R/Rust /USER/Foo_Bar"),
+            [user("alice"), subreddit("rust"), user("foo_bar")]
+        );
+    }
+
+    #[test]
+    fn only_at_a_boundary() {
+        assert!(mentions("https://www.reddit.com/r/foo/comments/x").is_empty());
+        assert!(mentions("reddit.com/r/foo foo/u/bar fu/bar and/r/x r_/x").is_empty());
+        assert_eq!(
+            mentions("[r/foo](https://reddit.com/r/foo)"),
+            [subreddit("foo")]
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(mentions(r"u/some\_name"), [user("some_name")]);
+        assert_eq!(mentions("r/rust-lang"), [subreddit("rust")]);
+        assert_eq!(mentions("u/my-name"), [user("my-name")]);
+        assert!(mentions("u/ab r/a r/abcdefghijklmnopqrstuvwxyz u/").is_empty());
+    }
+
+    #[test]
+    fn each_target_once() {
+        assert_eq!(
+            mentions("r/rust /r/Rust R/rust u/rust u/Rust"),
+            [subreddit("rust"), user("rust")]
+        );
+    }
+
+    #[test]
+    fn post_title_and_self_text() {
+        let mut post = test_support::post("abc", "bob", "test", 1, 1);
+        post.title = "Crosspost from r/pics".to_string();
+        post.selftext = "thanks u/alice and r/Pics".to_string();
+        let found: Vec<_> = post_mentions(&post)
+            .into_iter()
+            .map(|mention| (mention.kind, mention.target))
+            .collect();
+        assert_eq!(found, [subreddit("pics"), user("alice")]);
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+
+/// A numbered schema change. Each backend keeps its scripts in its own `migrations` directory.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub struct AppliedMigration {
+    pub version: i32,
+    pub applied_at: String,
+}
+
+/// Implemented by each database backend. `apply` must run the script and record it in
+/// `schema_version` atomically.
+pub trait Migrator {
+    fn migrations(&self) -> &'static [Migration];
+    fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>>;
+    fn apply(&mut self, migration: &Migration) -> Result<()>;
+}
+
+/// Applies every migration newer than the current schema version, returning how many ran.
+pub fn migrate(migrator: &mut dyn Migrator) -> Result<usize> {
+    let current = current_version(migrator)?;
+    let mut count = 0;
+    for migration in migrator.migrations() {
+        if migration.version <= current {
+            continue;
+        }
+        println!(
+            "Applying migration {:04} {}",
+            migration.version, migration.name
+        );
+        migrator.apply(migration).with_context(|| {
+            format!(
+                "Migration {:04} {} failed",
+                migration.version, migration.name
+            )
+        })?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+pub fn current_version(migrator: &mut dyn Migrator) -> Result<i32> {
+    Ok(migrator
+        .applied_migrations()?
+        .iter()
+        .map(|applied| applied.version)
+        .max()
+        .unwrap_or(0))
+}
+
+/// Prints every known migration and whether it has been applied.
+pub fn print_status(migrator: &mut dyn Migrator) -> Result<()> {
+    let applied = migrator.applied_migrations()?;
+    let latest = migrator.migrations().last().map_or(0, |m| m.version);
+    let current = applied.iter().map(|a| a.version).max().unwrap_or(0);
+    println!("Schema version {} of {}", current, latest);
+    for migration in migrator.migrations() {
+        let state = match applied.iter().find(|a| a.version == migration.version) {
+            Some(applied) => format!("applied {}", applied.applied_at),
+            None => "pending".to_string(),
+        };
+        println!(
+            "  {:04} {:<24} {}",
+            migration.version, migration.name, state
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "one",
+            sql: "",
+        },
+        Migration {
+            version: 2,
+            name: "two",
+            sql: "",
+        },
+        Migration {
+            version: 3,
+            name: "broken",
+            sql: "",
+        },
+    ];
+
+    /// Keeps the applied versions in memory and fails on migrations named `broken`.
+    struct Fake {
+        migrations: &'static [Migration],
+        applied: Vec<i32>,
+    }
+
+    impl Migrator for Fake {
+        fn migrations(&self) -> &'static [Migration] {
+            self.migrations
+        }
+
+        fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>> {
+            Ok(self
+                .applied
+                .iter()
+                .map(|&version| AppliedMigration {
+                    version,
+                    applied_at: String::new(),
+                })
+                .collect())
+        }
+
+        fn apply(&mut self, migration: &Migration) -> Result<()> {
+            if migration.name == "broken" {
+                bail!("syntax error");
+            }
+            self.applied.push(migration.version);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn applies_pending_migrations_once() {
+        let mut fake = Fake {
+            migrations: &MIGRATIONS[..2],
+            applied: vec![1],
+        };
+        assert_eq!(current_version(&mut fake).unwrap(), 1);
+        assert_eq!(migrate(&mut fake).unwrap(), 1);
+        assert_eq!(fake.applied, [1, 2]);
+        assert_eq!(migrate(&mut fake).unwrap(), 0);
+    }
+
+    #[test]
+    fn stops_at_a_failed_migration() {
+        let mut fake = Fake {
+            migrations: MIGRATIONS,
+            applied: Vec::new(),
+        };
+        let err = migrate(&mut fake).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Migration 0003 broken failed: syntax error"
+        );
+        assert_eq!(current_version(&mut fake).unwrap(), 2);
+    }
+
+    #[test]
+    fn sqlite_schema_is_current_after_migrating() {
+        let mut db = crate::database::Database::open(":memory:").unwrap();
+        assert!(db.pending_migrations().unwrap() > 0);
+        db.migrate().unwrap();
+        assert_eq!(db.pending_migrations().unwrap(), 0);
+        assert_eq!(db.migrate().unwrap(), 0);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bzip2::write::BzEncoder;
+use flate2::write::GzEncoder;
+use xz2::write::XzEncoder;
+
+/// Compression picked from the last extension of an output file, the same ones the importer
+/// reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression of `path` and the path with the compression extension removed.
+    pub fn from_path(path: &Path) -> (Compression, &Path) {
+        let compression = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("bz2") => Compression::Bzip2,
+            Some("xz") => Compression::Xz,
+            Some("zst") => Compression::Zstd,
+            _ => return (Compression::None, path),
+        };
+        (compression, path.file_stem().map_or(path, Path::new))
+    }
+}
+
+/// A file writer that compresses what is written to it.
+pub enum Encoder {
+    None(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Bzip2(BzEncoder<BufWriter<File>>),
+    Xz(XzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Encoder {
+    pub fn new(file: File, compression: Compression) -> io::Result<Self> {
+        let file = BufWriter::new(file);
+        Ok(match compression {
+            Compression::None => Encoder::None(file),
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Compression::Bzip2 => {
+                Encoder::Bzip2(BzEncoder::new(file, bzip2::Compression::default()))
+            }
+            Compression::Xz => Encoder::Xz(XzEncoder::new(file, 6)),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Writes the end of the compressed stream and flushes the file.
+    pub fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            Encoder::None(file) => file,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Bzip2(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(w) => w.write(buf),
+            Encoder::Gzip(w) => w.write(buf),
+            Encoder::Bzip2(w) => w.write(buf),
+            Encoder::Xz(w) => w.write(buf),
+            Encoder::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(w) => w.flush(),
+            Encoder::Gzip(w) => w.flush(),
+            Encoder::Bzip2(w) => w.flush(),
+            Encoder::Xz(w) => w.flush(),
+            Encoder::Zstd(w) => w.flush(),
+        }
+    }
+}
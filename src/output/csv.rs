@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Result};
+
+use super::{
+    compress::{Compression, Encoder},
+    Column, PartialFile, RowWriter,
+};
+use crate::value::Value;
+
+pub struct CsvWriter {
+    file: PartialFile,
+    writer: csv::Writer<Encoder>,
+    rows: usize,
+}
+
+impl CsvWriter {
+    /// Fields containing the delimiter, quotes or line breaks (multi-line bodies) are quoted.
+    pub fn new(
+        file: PartialFile,
+        columns: &[Column],
+        delimiter: u8,
+        header: bool,
+        compression: Compression,
+    ) -> Result<Self> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(Encoder::new(file.create()?, compression)?);
+        if header {
+            writer.write_record(columns.iter().map(|column| column.name.as_str()))?;
+        }
+        Ok(CsvWriter {
+            file,
+            writer,
+            rows: 0,
+        })
+    }
+}
+
+impl RowWriter for CsvWriter {
+    fn write(&mut self, row: &[Value]) -> Result<()> {
+        for value in row {
+            match value {
+                Value::Null => self.writer.write_field("")?,
+                Value::Integer(i) => self.writer.write_field(i.to_string())?,
+                Value::Real(f) => self.writer.write_field(f.to_string())?,
+                Value::Text(s) => self.writer.write_field(s)?,
+            }
+        }
+        self.writer.write_record(None::<&[u8]>)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<usize> {
+        let encoder = self
+            .writer
+            .into_inner()
+            .map_err(|err| anyhow!("{}", err.error()))?;
+        encoder.finish()?;
+        self.file.commit()?;
+        Ok(self.rows)
+    }
+}
@@ -0,0 +1,279 @@
+//! Edge lists written as GraphML or GEXF, for Gephi, networkx, igraph and friends. Nodes are
+//! the distinct sources and targets, labelled with their name.
+
+use std::{io::Write, path::Path};
+
+use anyhow::{bail, Result};
+use hashbrown::HashSet;
+
+use super::{
+    compress::{Compression, Encoder},
+    Column, Kind, PartialFile,
+};
+use crate::value::Value;
+
+/// Whether `path` is a graph file this module writes, going by its extension.
+pub fn is_graph(path: &Path) -> bool {
+    let (_, name) = Compression::from_path(path);
+    matches!(
+        name.extension().and_then(|ext| ext.to_str()),
+        Some("graphml") | Some("gexf")
+    )
+}
+
+/// Writes a directed graph to `path`. Each edge is a row of `columns`: the source and target
+/// node names, then edge attributes. The attribute named by `weight` is also written as the GEXF
+/// edge weight.
+pub fn write(
+    path: &Path,
+    columns: &[Column],
+    edges: &[Vec<Value>],
+    weight: Option<&str>,
+) -> Result<()> {
+    let (compression, name) = Compression::from_path(path);
+    let file = PartialFile::new(path);
+    let mut out = Encoder::new(file.create()?, compression)?;
+    let nodes = nodes(edges);
+    match name.extension().and_then(|ext| ext.to_str()) {
+        Some("graphml") => graphml(&mut out, columns, &nodes, edges)?,
+        Some("gexf") => {
+            let weight = weight.and_then(|name| columns.iter().position(|c| c.name == name));
+            gexf(&mut out, columns, &nodes, edges, weight)?
+        }
+        _ => bail!("{} is not a .graphml or .gexf file", path.display()),
+    }
+    out.finish()?;
+    file.commit()?;
+    Ok(())
+}
+
+/// Node names in order of first appearance.
+fn nodes(edges: &[Vec<Value>]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    for edge in edges {
+        for value in &edge[..2] {
+            let name = value.to_text();
+            if seen.insert(name.clone()) {
+                nodes.push(name);
+            }
+        }
+    }
+    nodes
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn graphml(
+    out: &mut impl Write,
+    columns: &[Column],
+    nodes: &[String],
+    edges: &[Vec<Value>],
+) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    for (i, column) in columns.iter().enumerate().skip(2) {
+        let kind = match column.kind {
+            Kind::Text => "string",
+            Kind::Integer => "long",
+            Kind::Real => "double",
+        };
+        writeln!(
+            out,
+            r#"  <key id="e{}" for="edge" attr.name="{}" attr.type="{}"/>"#,
+            i,
+            escape(&column.name),
+            kind
+        )?;
+    }
+    writeln!(out, r#"  <graph id="G" edgedefault="directed">"#)?;
+    for node in nodes {
+        let node = escape(node);
+        writeln!(
+            out,
+            r#"    <node id="{0}"><data key="label">{0}</data></node>"#,
+            node
+        )?;
+    }
+    for edge in edges {
+        write!(
+            out,
+            r#"    <edge source="{}" target="{}">"#,
+            escape(&edge[0].to_text()),
+            escape(&edge[1].to_text())
+        )?;
+        for (i, value) in edge.iter().enumerate().skip(2) {
+            if *value != Value::Null {
+                write!(
+                    out,
+                    r#"<data key="e{}">{}</data>"#,
+                    i,
+                    escape(&value.to_text())
+                )?;
+            }
+        }
+        writeln!(out, "</edge>")?;
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    Ok(())
+}
+
+fn gexf(
+    out: &mut impl Write,
+    columns: &[Column],
+    nodes: &[String],
+    edges: &[Vec<Value>],
+    weight: Option<usize>,
+) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gexf xmlns="http://gexf.net/1.3" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://gexf.net/1.3 http://gexf.net/1.3/gexf.xsd" version="1.3">"#
+    )?;
+    writeln!(out, r#"  <graph defaultedgetype="directed" mode="static">"#)?;
+    writeln!(out, r#"    <attributes class="edge">"#)?;
+    for (i, column) in columns.iter().enumerate().skip(2) {
+        let kind = match column.kind {
+            Kind::Text => "string",
+            Kind::Integer => "long",
+            Kind::Real => "double",
+        };
+        writeln!(
+            out,
+            r#"      <attribute id="{}" title="{}" type="{}"/>"#,
+            i,
+            escape(&column.name),
+            kind
+        )?;
+    }
+    writeln!(out, "    </attributes>")?;
+    writeln!(out, "    <nodes>")?;
+    for node in nodes {
+        let node = escape(node);
+        writeln!(out, r#"      <node id="{0}" label="{0}"/>"#, node)?;
+    }
+    writeln!(out, "    </nodes>")?;
+    writeln!(out, "    <edges>")?;
+    for (id, edge) in edges.iter().enumerate() {
+        write!(
+            out,
+            r#"      <edge id="{}" source="{}" target="{}""#,
+            id,
+            escape(&edge[0].to_text()),
+            escape(&edge[1].to_text())
+        )?;
+        if let Some(weight) = weight {
+            write!(out, r#" weight="{}""#, edge[weight].to_text())?;
+        }
+        write!(out, "><attvalues>")?;
+        for (i, value) in edge.iter().enumerate().skip(2) {
+            if *value != Value::Null {
+                write!(
+                    out,
+                    r#"<attvalue for="{}" value="{}"/>"#,
+                    i,
+                    escape(&value.to_text())
+                )?;
+            }
+        }
+        writeln!(out, "</attvalues></edge>")?;
+    }
+    writeln!(out, "    </edges>")?;
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> (Vec<Column>, Vec<Vec<Value>>) {
+        let columns = vec![
+            Column::new("source", Kind::Text),
+            Column::new("target", Kind::Text),
+            Column::new("count", Kind::Integer),
+            Column::new("note", Kind::Text),
+        ];
+        let edge = |source: &str, target: &str, count: i64, note: Value| {
+            vec![
+                Value::Text(source.to_string()),
+                Value::Text(target.to_string()),
+                Value::Integer(count),
+                note,
+            ]
+        };
+        let edges = vec![
+            edge("a&b", "c", 2, Value::Text("<\"x'>\u{1}".to_string())),
+            edge("c", "a&b", 1, Value::Null),
+        ];
+        (columns, edges)
+    }
+
+    #[test]
+    fn graph_extensions() {
+        assert!(is_graph(Path::new("replies.graphml")));
+        assert!(is_graph(Path::new("replies.gexf.gz")));
+        assert!(!is_graph(Path::new("replies.csv")));
+        assert!(!is_graph(Path::new("graphml")));
+    }
+
+    #[test]
+    fn escapes_xml() {
+        assert_eq!(
+            escape("a&b <c> \"d\" 'e'\t\u{7}"),
+            "a&amp;b &lt;c&gt; &quot;d&quot; &apos;e&apos;\t"
+        );
+    }
+
+    #[test]
+    fn graphml_edges() {
+        let (columns, edges) = graph();
+        let mut out = Vec::new();
+        graphml(&mut out, &columns, &nodes(&edges), &edges).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(r#"<key id="e2" for="edge" attr.name="count" attr.type="long"/>"#));
+        assert_eq!(out.matches("<node ").count(), 2);
+        assert!(out.contains(r#"<node id="a&amp;b"><data key="label">a&amp;b</data></node>"#));
+        assert!(out.contains(
+            r#"<edge source="a&amp;b" target="c"><data key="e2">2</data><data key="e3">&lt;&quot;x&apos;&gt;</data></edge>"#
+        ));
+        // Null attributes are left out.
+        assert!(out.contains(r#"<edge source="c" target="a&amp;b"><data key="e2">1</data></edge>"#));
+    }
+
+    #[test]
+    fn gexf_weights() {
+        let (columns, edges) = graph();
+        let mut out = Vec::new();
+        gexf(&mut out, &columns, &nodes(&edges), &edges, Some(2)).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(r#"<attribute id="3" title="note" type="string"/>"#));
+        assert!(out.contains(
+            r#"<edge id="1" source="c" target="a&amp;b" weight="1"><attvalues><attvalue for="2" value="1"/></attvalues></edge>"#
+        ));
+    }
+}
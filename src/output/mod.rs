@@ -0,0 +1,140 @@
+//! Writing rows to files instead of a database table. The format is picked from the file
+//! extension: `.csv`, `.tsv`, `.json` (one object per line, also `.jsonl` or `.ndjson`) or
+//! `.parquet`. Text formats can be compressed by adding `.gz`, `.bz2`, `.xz` or `.zst`.
+
+pub mod compress;
+mod csv;
+pub mod graph;
+mod ndjson;
+pub mod parquet;
+pub mod sparse;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+
+pub use self::ndjson::json_object;
+
+use self::compress::Compression;
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Text,
+    Integer,
+    Real,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub kind: Kind,
+}
+
+impl Column {
+    pub fn new(name: &str, kind: Kind) -> Self {
+        Column {
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+pub trait RowWriter {
+    /// Writes one row, with a value for every column.
+    fn write(&mut self, row: &[Value]) -> Result<()>;
+
+    /// Flushes everything and moves the file into place. Returns the number of rows written.
+    fn finish(self: Box<Self>) -> Result<usize>;
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Start CSV and TSV files with a row of column names.
+    pub header: bool,
+    pub parquet: parquet::ParquetOptions,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            header: true,
+            parquet: parquet::ParquetOptions::default(),
+        }
+    }
+}
+
+/// Creates a writer for `path` with the given columns and default options.
+pub fn create(path: &Path, columns: Vec<Column>) -> Result<Box<dyn RowWriter>> {
+    create_with(path, columns, &WriteOptions::default())
+}
+
+pub fn create_with(
+    path: &Path,
+    columns: Vec<Column>,
+    options: &WriteOptions,
+) -> Result<Box<dyn RowWriter>> {
+    let file = PartialFile::new(path);
+    let (compression, name) = Compression::from_path(path);
+    match name.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => Ok(Box::new(csv::CsvWriter::new(
+            file,
+            &columns,
+            b',',
+            options.header,
+            compression,
+        )?)),
+        Some("tsv") => Ok(Box::new(csv::CsvWriter::new(
+            file,
+            &columns,
+            b'\t',
+            options.header,
+            compression,
+        )?)),
+        Some("json") | Some("jsonl") | Some("ndjson") => Ok(Box::new(ndjson::NdjsonWriter::new(
+            file,
+            columns,
+            compression,
+        )?)),
+        Some("parquet") if compression == Compression::None => Ok(Box::new(
+            parquet::ParquetWriter::new(file, columns, &options.parquet)?,
+        )),
+        _ => bail!(
+            "Can't tell the output format of {}, use .csv, .tsv, .json or .parquet",
+            path.display()
+        ),
+    }
+}
+
+/// Output is written next to its final path and only renamed into place once complete, so an
+/// interrupted run never leaves a truncated file behind.
+pub struct PartialFile {
+    path: PathBuf,
+    partial: PathBuf,
+}
+
+impl PartialFile {
+    pub fn new(path: &Path) -> Self {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        PartialFile {
+            path: path.to_path_buf(),
+            partial: PathBuf::from(partial),
+        }
+    }
+
+    pub fn create(&self) -> Result<fs::File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(fs::File::create(&self.partial)?)
+    }
+
+    pub fn commit(&self) -> Result<()> {
+        fs::rename(&self.partial, &self.path)?;
+        Ok(())
+    }
+}
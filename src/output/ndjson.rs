@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{
+    compress::{Compression, Encoder},
+    Column, PartialFile, RowWriter,
+};
+use crate::value::Value;
+
+/// A JSON object with a member per column, in column order.
+pub fn json_object(columns: &[Column], row: &[Value]) -> String {
+    let mut object = String::from("{");
+    for (i, (column, value)) in columns.iter().zip(row).enumerate() {
+        if i > 0 {
+            object.push(',');
+        }
+        object.push_str(&serde_json::Value::from(column.name.as_str()).to_string());
+        object.push(':');
+        let value = match value {
+            Value::Null => serde_json::Value::Null,
+            Value::Integer(i) => (*i).into(),
+            Value::Real(f) => (*f).into(),
+            Value::Text(s) => s.as_str().into(),
+        };
+        object.push_str(&value.to_string());
+    }
+    object.push('}');
+    object
+}
+
+/// One JSON object per line, keyed by column name in column order.
+pub struct NdjsonWriter {
+    file: PartialFile,
+    encoder: Encoder,
+    columns: Vec<Column>,
+    rows: usize,
+}
+
+impl NdjsonWriter {
+    pub fn new(file: PartialFile, columns: Vec<Column>, compression: Compression) -> Result<Self> {
+        let encoder = Encoder::new(file.create()?, compression)?;
+        Ok(NdjsonWriter {
+            file,
+            encoder,
+            columns,
+            rows: 0,
+        })
+    }
+}
+
+impl RowWriter for NdjsonWriter {
+    fn write(&mut self, row: &[Value]) -> Result<()> {
+        let mut line = json_object(&self.columns, row);
+        line.push('\n');
+        self.encoder.write_all(line.as_bytes())?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<usize> {
+        self.encoder.finish()?;
+        self.file.commit()?;
+        Ok(self.rows)
+    }
+}
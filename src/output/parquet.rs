@@ -0,0 +1,185 @@
+use std::{fs::File, sync::Arc};
+
+use anyhow::{bail, Result};
+use parquet::{
+    basic::{Compression, GzipLevel, LogicalType, Repetition, Type as PhysicalType, ZstdLevel},
+    data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type},
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedColumnWriter, SerializedFileWriter},
+    },
+    schema::types::Type,
+};
+
+use super::{Column, Kind, PartialFile, RowWriter};
+use crate::value::Value;
+
+/// Settings for Parquet files.
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    /// Rows buffered in memory before they are written out as a row group.
+    pub row_group_size: usize,
+    pub compression: Compression,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        ParquetOptions {
+            row_group_size: 100_000,
+            compression: Compression::SNAPPY,
+        }
+    }
+}
+
+impl ParquetOptions {
+    /// Parses `none`, `snappy`, `gzip` or `zstd`, optionally followed by a level, like `zstd:9`.
+    pub fn parse_compression(text: &str) -> Result<Compression> {
+        let (name, level) = match text.split_once(':') {
+            Some((name, level)) => (name, Some(level.parse::<u32>()?)),
+            None => (text, None),
+        };
+        Ok(match name {
+            "none" => Compression::UNCOMPRESSED,
+            "snappy" => Compression::SNAPPY,
+            "gzip" => Compression::GZIP(match level {
+                Some(level) => GzipLevel::try_new(level)?,
+                None => GzipLevel::default(),
+            }),
+            "zstd" => Compression::ZSTD(match level {
+                Some(level) => ZstdLevel::try_new(level as i32)?,
+                None => ZstdLevel::default(),
+            }),
+            _ => bail!(
+                "Unknown compression {}, use none, snappy, gzip or zstd",
+                text
+            ),
+        })
+    }
+}
+
+pub struct ParquetWriter {
+    file: PartialFile,
+    writer: SerializedFileWriter<File>,
+    columns: Vec<Column>,
+    row_group_size: usize,
+    buffered: Vec<Vec<Value>>,
+    rows: usize,
+}
+
+impl ParquetWriter {
+    pub fn new(file: PartialFile, columns: Vec<Column>, options: &ParquetOptions) -> Result<Self> {
+        let fields = columns
+            .iter()
+            .map(|column| {
+                let builder = match column.kind {
+                    Kind::Text => {
+                        Type::primitive_type_builder(&column.name, PhysicalType::BYTE_ARRAY)
+                            .with_logical_type(Some(LogicalType::String))
+                    }
+                    Kind::Integer => {
+                        Type::primitive_type_builder(&column.name, PhysicalType::INT64)
+                    }
+                    Kind::Real => Type::primitive_type_builder(&column.name, PhysicalType::DOUBLE),
+                };
+                Ok(Arc::new(
+                    builder.with_repetition(Repetition::OPTIONAL).build()?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()?;
+        let properties = WriterProperties::builder()
+            .set_compression(options.compression)
+            .set_max_row_group_size(options.row_group_size)
+            .build();
+        let writer =
+            SerializedFileWriter::new(file.create()?, Arc::new(schema), Arc::new(properties))?;
+        Ok(ParquetWriter {
+            file,
+            writer,
+            columns,
+            row_group_size: options.row_group_size,
+            buffered: Vec::new(),
+            rows: 0,
+        })
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        if self.buffered.is_empty() {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            let values = self.buffered.iter().map(|row| &row[index]);
+            match self.columns[index].kind {
+                Kind::Text => write_column::<ByteArrayType>(
+                    &mut column,
+                    values.map(|value| match value {
+                        Value::Text(s) => Some(ByteArray::from(s.as_str())),
+                        Value::Integer(i) => Some(ByteArray::from(i.to_string().as_str())),
+                        Value::Real(f) => Some(ByteArray::from(f.to_string().as_str())),
+                        Value::Null => None,
+                    }),
+                )?,
+                Kind::Integer => write_column::<Int64Type>(
+                    &mut column,
+                    values.map(|value| match value {
+                        Value::Integer(i) => Some(*i),
+                        Value::Real(f) => Some(*f as i64),
+                        Value::Text(s) => s.parse().ok(),
+                        Value::Null => None,
+                    }),
+                )?,
+                Kind::Real => write_column::<DoubleType>(
+                    &mut column,
+                    values.map(|value| match value {
+                        Value::Real(f) => Some(*f),
+                        Value::Integer(i) => Some(*i as f64),
+                        Value::Text(s) => s.parse().ok(),
+                        Value::Null => None,
+                    }),
+                )?,
+            }
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+        self.buffered.clear();
+        Ok(())
+    }
+}
+
+/// Writes one column of a row group. Every column is optional: definition level 1 for a value,
+/// 0 for NULL.
+fn write_column<T: DataType>(
+    column: &mut SerializedColumnWriter<'_>,
+    values: impl Iterator<Item = Option<T::T>>,
+) -> Result<()> {
+    let values: Vec<Option<T::T>> = values.collect();
+    let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+    let data: Vec<T::T> = values.into_iter().flatten().collect();
+    column
+        .typed::<T>()
+        .write_batch(&data, Some(&levels), None)?;
+    Ok(())
+}
+
+impl RowWriter for ParquetWriter {
+    fn write(&mut self, row: &[Value]) -> Result<()> {
+        self.buffered.push(row.to_vec());
+        self.rows += 1;
+        if self.buffered.len() >= self.row_group_size {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<usize> {
+        self.write_row_group()?;
+        self.writer.close()?;
+        self.file.commit()?;
+        Ok(self.rows)
+    }
+}
@@ -0,0 +1,208 @@
+//! Sparse integer matrices written as Matrix Market coordinate files or as the `.npz` files
+//! `scipy.sparse.save_npz` writes, so `scipy.sparse.load_npz` reads them as a `csr_matrix`.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use super::PartialFile;
+
+/// A matrix in compressed sparse row form. The entries of row `r` are `indices[indptr[r]..
+/// indptr[r + 1]]` and the same range of `data`, with column indices ascending.
+#[derive(Debug, Clone, Default)]
+pub struct Csr {
+    pub rows: usize,
+    pub columns: usize,
+    pub indptr: Vec<i64>,
+    pub indices: Vec<i32>,
+    pub data: Vec<i64>,
+}
+
+impl Csr {
+    /// Builds the matrix from `(row, column, value)` entries in any order.
+    pub fn from_entries(rows: usize, columns: usize, mut entries: Vec<(u32, u32, i64)>) -> Self {
+        entries.sort_unstable_by_key(|&(row, column, _)| (row, column));
+        let mut indptr = vec![0; rows + 1];
+        for &(row, _, _) in &entries {
+            indptr[row as usize + 1] += 1;
+        }
+        for row in 0..rows {
+            indptr[row + 1] += indptr[row];
+        }
+        Csr {
+            rows,
+            columns,
+            indptr,
+            indices: entries
+                .iter()
+                .map(|&(_, column, _)| column as i32)
+                .collect(),
+            data: entries.iter().map(|&(_, _, value)| value).collect(),
+        }
+    }
+
+    /// Writes a Matrix Market coordinate file, with `comment` on `%` lines after the banner.
+    pub fn write_mtx(&self, path: &Path, comment: &str) -> Result<()> {
+        let file = PartialFile::new(path);
+        let mut out = BufWriter::new(file.create()?);
+        writeln!(out, "%%MatrixMarket matrix coordinate integer general")?;
+        for line in comment.lines() {
+            writeln!(out, "% {}", line)?;
+        }
+        writeln!(out, "{} {} {}", self.rows, self.columns, self.data.len())?;
+        for row in 0..self.rows {
+            let (start, end) = (self.indptr[row] as usize, self.indptr[row + 1] as usize);
+            for i in start..end {
+                // Matrix Market indices start at 1.
+                writeln!(out, "{} {} {}", row + 1, self.indices[i] + 1, self.data[i])?;
+            }
+        }
+        out.flush()?;
+        drop(out);
+        file.commit()
+    }
+
+    /// Writes the arrays of a `scipy.sparse.csr_matrix` as `save_npz` does.
+    pub fn write_npz(&self, path: &Path) -> Result<()> {
+        let file = PartialFile::new(path);
+        let mut zip = ZipWriter::new(file.create()?);
+        // Zip64, so arrays over 4 GiB can be written.
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(true);
+        let entries: [(&str, Vec<u8>); 5] = [
+            ("indices.npy", npy_i32(&self.indices)),
+            ("indptr.npy", npy_i64(&self.indptr)),
+            ("format.npy", npy_str("csr")),
+            (
+                "shape.npy",
+                npy_i64(&[self.rows as i64, self.columns as i64]),
+            ),
+            ("data.npy", npy_i64(&self.data)),
+        ];
+        for (name, bytes) in &entries {
+            zip.start_file(*name, options)?;
+            zip.write_all(bytes)?;
+        }
+        let out: File = zip.finish()?;
+        out.sync_all()?;
+        file.commit()
+    }
+}
+
+/// A `.npy` file: magic, version 1.0, then a header padded so the data starts at a multiple of
+/// 64 bytes.
+fn npy(descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+fn npy_i64(values: &[i64]) -> Vec<u8> {
+    let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    npy("<i8", &format!("({},)", values.len()), &data)
+}
+
+fn npy_i32(values: &[i32]) -> Vec<u8> {
+    let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    npy("<i4", &format!("({},)", values.len()), &data)
+}
+
+/// A 0-d unicode array, which numpy stores as UTF-32.
+fn npy_str(value: &str) -> Vec<u8> {
+    let data: Vec<u8> = value
+        .chars()
+        .flat_map(|c| (c as u32).to_le_bytes())
+        .collect();
+    npy(&format!("<U{}", value.chars().count()), "()", &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn matrix() -> Csr {
+        // [[0, 5, 0],
+        //  [0, 0, 0],
+        //  [7, 0, 9]]
+        Csr::from_entries(3, 3, vec![(2, 2, 9), (0, 1, 5), (2, 0, 7)])
+    }
+
+    #[test]
+    fn csr_layout() {
+        let csr = matrix();
+        assert_eq!(csr.indptr, [0, 1, 1, 3]);
+        assert_eq!(csr.indices, [1, 0, 2]);
+        assert_eq!(csr.data, [5, 7, 9]);
+    }
+
+    #[test]
+    fn npy_header() {
+        let bytes = npy_i32(&[1, -2]);
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<i4', 'fortran_order': False, 'shape': (2,), }"));
+        assert!(header.ends_with(" \n"));
+        assert_eq!(&bytes[10 + header_len..], [1, 0, 0, 0, 254, 255, 255, 255]);
+
+        let bytes = npy_str("csr");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'descr': '<U3'") && header.contains("'shape': ()"));
+        assert_eq!(&bytes[10 + header_len..], b"c\0\0\0s\0\0\0r\0\0\0");
+    }
+
+    #[test]
+    fn npz_and_mtx_files() {
+        let temp = TempDir::new("sparse");
+        let dir = temp.path();
+        let csr = matrix();
+        csr.write_npz(&dir.join("m.npz")).unwrap();
+        csr.write_mtx(&dir.join("m.mtx"), "counts").unwrap();
+
+        let mut zip = zip::ZipArchive::new(File::open(dir.join("m.npz")).unwrap()).unwrap();
+        let mut names: Vec<_> = zip.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "data.npy",
+                "format.npy",
+                "indices.npy",
+                "indptr.npy",
+                "shape.npy"
+            ]
+        );
+        let mut shape = Vec::new();
+        zip.by_name("shape.npy")
+            .unwrap()
+            .read_to_end(&mut shape)
+            .unwrap();
+        assert_eq!(shape, npy_i64(&[3, 3]));
+
+        let mtx = std::fs::read_to_string(dir.join("m.mtx")).unwrap();
+        assert_eq!(
+            mtx,
+            "%%MatrixMarket matrix coordinate integer general\n% counts\n3 3 3\n1 2 5\n3 1 7\n3 3 9\n"
+        );
+    }
+}
@@ -1,18 +1,34 @@
 use anyhow::Context;
 use serde::Deserialize;
 
-// author, author_flair_text, created_utc, retrieved_on, title, url, subreddit, id, score
+use crate::{flair::Flair, link::Link, mention::Mention};
+
+// author, author_flair_text, created_utc, retrieved_on, title, selftext, url, subreddit, id, score
 #[derive(Deserialize, Debug, Clone)]
 pub struct Post {
     pub id: String,
     pub author: Option<String>,
-    pub author_flair_text: Option<String>, // converting this into our code before we push to db
+    pub author_flair_text: Option<String>,
+    #[serde(default)]
+    pub author_flair_css_class: Option<String>,
     pub created_utc: i32,
     pub retrieved_on: Option<i32>,
     pub title: String,
+    #[serde(default)]
+    pub selftext: String,
     pub url: String,
     pub subreddit: Option<String>,
     pub score: i32,
+    /// Mapped `author_flair_text`, filled in from the flair mapping during the import.
+    #[serde(skip)]
+    pub flair: Flair,
+    /// Links in `url` and `selftext`, filled in during database imports.
+    #[serde(skip)]
+    pub links: Vec<Link>,
+    /// Users and subreddits mentioned in `title` and `selftext`, filled in during database
+    /// imports.
+    #[serde(skip)]
+    pub mentions: Vec<Mention>,
 }
 
 impl Post {
@@ -37,10 +53,13 @@ impl Post {
                 *author = "".into()
             }
         }
-        let mut post = Post::deserialize(json)
+        if let Some(selftext) = json.get_mut("selftext") {
+            if matches!(selftext, serde_json::Value::Null) {
+                *selftext = "".into()
+            }
+        }
+        Post::deserialize(json)
             .with_context(|| format!("Failed to deserialize line: {}", line))
-            .unwrap();
-
-        post
+            .unwrap()
     }
-}
\ No newline at end of file
+}
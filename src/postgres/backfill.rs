@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+use super::{copy::copy_row, Postgres};
+use crate::{
+    backfill::{Backfill, UpdateCounts},
+    value::Value,
+};
+
+// Staged rows applied per UPDATE statement.
+pub(super) const BACKFILL_BATCH_SIZE: usize = 100_000;
+
+impl Postgres {
+    /// Sets up the staging table for `backfill`. Its columns copy the types of the target
+    /// columns, so COPY parses the dump values the same way an insert would. Fails if the table
+    /// lacks one of the columns.
+    pub fn start_backfill(&mut self, backfill: Backfill) -> Result<()> {
+        self.flush_backfill()?;
+        self.connection
+            .batch_execute(&format!(
+                "DROP TABLE IF EXISTS pg_temp.backfill_stage; \
+                 CREATE TEMP TABLE backfill_stage AS SELECT {}, {} FROM {} WITH NO DATA;",
+                backfill.key,
+                backfill.columns.join(", "),
+                backfill.table
+            ))
+            .with_context(|| format!("Can't backfill {}", backfill.table))?;
+        self.backfill = Some(backfill);
+        Ok(())
+    }
+
+    /// Queues new values for the row with reddit id `key`. A row staged twice keeps the last
+    /// values.
+    pub fn stage_backfill(&mut self, key: String, values: Vec<Value>) -> Result<()> {
+        self.staged.insert(key, values);
+        if self.staged.len() >= BACKFILL_BATCH_SIZE {
+            self.flush_backfill()?;
+        }
+        Ok(())
+    }
+
+    /// Copies the staged rows into the staging table and applies them with a single
+    /// `UPDATE ... FROM`, instead of one statement per row.
+    pub fn flush_backfill(&mut self) -> Result<()> {
+        let backfill = match &self.backfill {
+            Some(backfill) if !self.staged.is_empty() => backfill,
+            _ => return Ok(()),
+        };
+        let mut transaction = self.connection.transaction()?;
+        transaction.batch_execute("TRUNCATE backfill_stage")?;
+        let mut writer = transaction.copy_in("COPY backfill_stage FROM STDIN")?;
+        let mut line = String::new();
+        for (key, values) in self.staged.drain() {
+            line.clear();
+            copy_row(
+                &mut line,
+                &[&[Value::Text(key)], values.as_slice()].concat(),
+            );
+            writer.write_all(line.as_bytes())?;
+        }
+        writer.finish()?;
+        transaction.batch_execute("ANALYZE backfill_stage")?;
+        transaction.execute(
+            format!(
+                "UPDATE {0} SET {1} FROM backfill_stage s WHERE {0}.{2} = s.{2}",
+                backfill.table,
+                backfill.assignments(),
+                backfill.key
+            )
+            .as_str(),
+            &[],
+        )?;
+        let row = transaction.query_one(
+            format!(
+                "SELECT count(*), count(*) FILTER (WHERE NOT EXISTS \
+                     (SELECT 1 FROM {0} WHERE {0}.{1} = s.{1})) \
+                 FROM backfill_stage s",
+                backfill.table, backfill.key
+            )
+            .as_str(),
+            &[],
+        )?;
+        transaction.commit()?;
+        let (staged, missing): (i64, i64) = (row.get(0), row.get(1));
+        let batch = UpdateCounts {
+            matched: (staged - missing) as usize,
+            missing: missing as usize,
+        };
+        batch.print("Batch");
+        self.update_counts.add(&batch);
+        Ok(())
+    }
+}
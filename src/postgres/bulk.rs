@@ -0,0 +1,99 @@
+use std::{thread, time::Instant};
+
+use anyhow::{anyhow, Result};
+use postgres::{Client, NoTls};
+
+use super::Postgres;
+use crate::{database::if_not_exists, progress::timed};
+
+impl Postgres {
+    /// Drops the secondary indexes of `table` ahead of a bulk import. With `fts` the generated
+    /// full text column goes too, `build_fts` adds it back once the data is in. Returns how many
+    /// indexes were dropped.
+    pub fn defer_indexes(&mut self, table: &str, fts: bool) -> Result<usize> {
+        let fts_index = format!("idx_{}_fts", table);
+        let mut transaction = self.connection.transaction()?;
+        // Indexes backing a primary key or unique constraint stay, they are part of the schema.
+        let rows = transaction.query(
+            "SELECT i.relname::text, pg_get_indexdef(i.oid) FROM pg_index x \
+             JOIN pg_class i ON i.oid = x.indexrelid \
+             JOIN pg_class t ON t.oid = x.indrelid \
+             WHERE t.relname = $1 AND t.relnamespace = current_schema()::regnamespace \
+             AND NOT EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = x.indexrelid)",
+            &[&table],
+        )?;
+        let mut count = 0;
+        for row in rows {
+            let name: String = row.get(0);
+            let sql: String = row.get(1);
+            if fts && name == fts_index {
+                continue;
+            }
+            transaction.execute(
+                "INSERT INTO deferred_index (name, tbl, sql) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                &[&name, &table, &sql],
+            )?;
+            println!("Dropping index {}", name);
+            transaction.batch_execute(&format!("DROP INDEX {}", name))?;
+            count += 1;
+        }
+        if fts {
+            println!("Dropping full text column {}.fts", table);
+            transaction
+                .batch_execute(&format!("ALTER TABLE {} DROP COLUMN IF EXISTS fts", table))?;
+        }
+        transaction.commit()?;
+        Ok(count)
+    }
+
+    /// Recreates everything dropped by `defer_indexes`, including by an earlier import that did
+    /// not finish. Each index is built on its own connection so they are created in parallel.
+    pub fn restore_indexes(&mut self) -> Result<()> {
+        let deferred: Vec<(String, String)> = self
+            .connection
+            .query("SELECT name, sql FROM deferred_index ORDER BY name", &[])?
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        if deferred.is_empty() {
+            return Ok(());
+        }
+        timed(&format!("Creating {} indexes", deferred.len()), || {
+            let handles: Vec<_> = deferred
+                .into_iter()
+                .map(|(name, sql)| {
+                    let url = self.url.clone();
+                    thread::spawn(move || -> Result<String> {
+                        println!("Creating index {}", name);
+                        let start = Instant::now();
+                        let mut connection = Client::connect(&url, NoTls)?;
+                        connection.batch_execute(&if_not_exists(&sql))?;
+                        connection
+                            .execute("DELETE FROM deferred_index WHERE name = $1", &[&name])?;
+                        println!("Created index {} in {:.1?}", name, start.elapsed());
+                        Ok(name)
+                    })
+                })
+                .collect();
+            let mut failed = Vec::new();
+            for handle in handles {
+                if let Err(err) = handle.join().expect("index thread panicked") {
+                    eprintln!("{:#}", err);
+                    failed.push(err);
+                }
+            }
+            match failed.len() {
+                0 => Ok(()),
+                n => Err(anyhow!("{} index(es) could not be created", n)),
+            }
+        })
+    }
+
+    pub fn analyze(&mut self, table: &str) -> Result<()> {
+        timed(&format!("Vacuuming and analyzing {}", table), || {
+            Ok(self
+                .connection
+                .batch_execute(&format!("VACUUM ANALYZE {}", table))?)
+        })
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::Postgres;
+use crate::{link::Link, mention::Mention, value::Value};
+
+// Comments and posts kept in memory before they are copied into their tables, along with the links
+// and mentions found in them. Like the Sqlite batches, each copy is one transaction.
+pub(super) const BATCH_SIZE: usize = 10_000;
+
+pub(super) const COMMENT_COLUMNS: &str = "reddit_id, author, subreddit, body, score, created_utc, \
+     retrieved_on, parent_id, parent_is_post, flair, flair_id, author_flair_text";
+
+pub(super) const POST_COLUMNS: &str = "pid, author, flair, created_utc, retrieved_on, title, \
+     selftext, url, subreddit, score, flair_id, author_flair_text";
+
+pub(super) const LINK_COLUMNS: &str = "reddit_id, type, url, domain";
+
+pub(super) const MENTION_COLUMNS: &str = "reddit_id, type, mention_type, target";
+
+impl Postgres {
+    /// Queues a comment row, in `COMMENT_COLUMNS` order, writing the queue out once it is full.
+    pub(super) fn queue_comment(&mut self, row: Vec<Value>) -> Result<()> {
+        self.comments.push(row);
+        if self.comments.len() >= BATCH_SIZE {
+            self.flush_rows()?;
+        }
+        Ok(())
+    }
+
+    /// Queues a post row, in `POST_COLUMNS` order, writing the queue out once it is full.
+    pub(super) fn queue_post(&mut self, row: Vec<Value>) -> Result<()> {
+        self.posts.push(row);
+        if self.posts.len() >= BATCH_SIZE {
+            self.flush_rows()?;
+        }
+        Ok(())
+    }
+
+    /// Queues the links and mentions found in a comment or post, to be copied in the same batch
+    /// as its row.
+    pub(super) fn queue_extracted(
+        &mut self,
+        reddit_id: &str,
+        kind: &str,
+        links: &[Link],
+        mentions: &[Mention],
+    ) {
+        let row = |a: &str, b: &str| {
+            vec![
+                Value::Text(reddit_id.to_string()),
+                Value::Text(kind.to_string()),
+                Value::Text(a.to_string()),
+                Value::Text(b.to_string()),
+            ]
+        };
+        self.links
+            .extend(links.iter().map(|link| row(&link.url, &link.domain)));
+        self.mentions.extend(
+            mentions
+                .iter()
+                .map(|mention| row(mention.kind, &mention.target)),
+        );
+    }
+
+    /// Copies the queued comments and posts, and the links and mentions found in them, into their
+    /// tables with `COPY ... FROM STDIN`. It all happens in one transaction, so a failed batch
+    /// leaves no links or mentions behind without their rows.
+    pub fn flush_rows(&mut self) -> Result<()> {
+        if [&self.comments, &self.posts, &self.links, &self.mentions]
+            .iter()
+            .all(|rows| rows.is_empty())
+        {
+            return Ok(());
+        }
+        let mut transaction = self.connection.transaction()?;
+        for (table, columns, rows) in [
+            ("comment", COMMENT_COLUMNS, &mut self.comments),
+            ("posts", POST_COLUMNS, &mut self.posts),
+            ("link", LINK_COLUMNS, &mut self.links),
+            ("mention", MENTION_COLUMNS, &mut self.mentions),
+        ] {
+            if rows.is_empty() {
+                continue;
+            }
+            let mut writer =
+                transaction.copy_in(format!("COPY {} ({}) FROM STDIN", table, columns).as_str())?;
+            let mut line = String::new();
+            for row in rows.drain(..) {
+                line.clear();
+                copy_row(&mut line, &row);
+                writer.write_all(line.as_bytes())?;
+            }
+            writer.finish()?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Appends `values` as one line of COPY's text format.
+pub(super) fn copy_row(line: &mut String, values: &[Value]) {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            line.push('\t');
+        }
+        copy_text(line, value);
+    }
+    line.push('\n');
+}
+
+/// Appends `value` in COPY's text format.
+pub(super) fn copy_text(line: &mut String, value: &Value) {
+    match value {
+        Value::Null => line.push_str("\\N"),
+        Value::Integer(i) => line.push_str(&i.to_string()),
+        Value::Real(f) => line.push_str(&f.to_string()),
+        Value::Text(s) => {
+            for c in s.chars() {
+                match c {
+                    '\\' => line.push_str("\\\\"),
+                    '\t' => line.push_str("\\t"),
+                    '\n' => line.push_str("\\n"),
+                    '\r' => line.push_str("\\r"),
+                    c => line.push(c),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_in_copy_text_format() {
+        let mut line = String::new();
+        copy_row(
+            &mut line,
+            &[
+                Value::Text("a\tb\\c\nd\re".to_string()),
+                Value::Integer(-3),
+                Value::Null,
+                Value::Real(1.5),
+            ],
+        );
+        assert_eq!(line, "a\\tb\\\\c\\nd\\re\t-3\t\\N\t1.5\n");
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+
+use super::Postgres;
+use crate::flair_history::{FlairChange, Span, DELETED};
+
+// Spans kept in memory before they are merged into author_flair_history.
+pub(super) const HISTORY_BATCH_SIZE: usize = 10_000;
+
+impl Postgres {
+    /// Merges the spans collected so far into `author_flair_history`.
+    pub fn flush_flair_history(&mut self) -> Result<()> {
+        if self.flair_history.is_empty() {
+            return Ok(());
+        }
+        let mut transaction = self.connection.transaction()?;
+        let statement = transaction.prepare(
+            "INSERT INTO author_flair_history (author, subreddit, flair_id, first_seen, last_seen, comment_count) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT (author, subreddit, flair_id) DO UPDATE SET \
+             first_seen = LEAST(author_flair_history.first_seen, EXCLUDED.first_seen), \
+             last_seen = GREATEST(author_flair_history.last_seen, EXCLUDED.last_seen), \
+             comment_count = author_flair_history.comment_count + EXCLUDED.comment_count",
+        )?;
+        for ((author, subreddit, flair_id), span) in self.flair_history.drain() {
+            transaction.execute(
+                &statement,
+                &[
+                    &author,
+                    &subreddit,
+                    &flair_id,
+                    &span.first_seen,
+                    &span.last_seen,
+                    &span.comment_count,
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Recomputes `author_flair_history` from the comment table, for example after
+    /// `update_flair` has changed the flair of comments that were already counted.
+    pub fn rebuild_flair_history(&mut self) -> Result<usize> {
+        self.flair_history.clear();
+        let mut transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM author_flair_history", &[])?;
+        let rows = transaction.execute(
+            "INSERT INTO author_flair_history (author, subreddit, flair_id, first_seen, last_seen, comment_count) \
+             SELECT author, subreddit, flair_id, min(created_utc), max(created_utc), count(*) FROM comment \
+             WHERE flair_id IS NOT NULL AND author <> $1 \
+             GROUP BY author, subreddit, flair_id",
+            &[&DELETED],
+        )?;
+        transaction.commit()?;
+        Ok(rows as usize)
+    }
+
+    /// Flair history of authors that used more than one flair in a subreddit.
+    pub fn flair_changes(&mut self, subreddit: Option<&str>) -> Result<Vec<FlairChange>> {
+        let rows = self.connection.query(
+            "SELECT h.author, h.subreddit, coalesce(f.label, f.text), h.first_seen, h.last_seen, h.comment_count \
+             FROM author_flair_history h JOIN flair f ON f.id = h.flair_id \
+             WHERE (h.author, h.subreddit) IN \
+                 (SELECT author, subreddit FROM author_flair_history \
+                  WHERE $1::text IS NULL OR subreddit = $1 \
+                  GROUP BY author, subreddit HAVING count(*) > 1) \
+             ORDER BY h.author, h.subreddit, h.first_seen",
+            &[&subreddit],
+        )?;
+        Ok(rows
+            .iter()
+            .map(|row| FlairChange {
+                author: row.get(0),
+                subreddit: row.get(1),
+                flair: row.get(2),
+                span: Span {
+                    first_seen: row.get(3),
+                    last_seen: row.get(4),
+                    comment_count: row.get(5),
+                },
+            })
+            .collect())
+    }
+}
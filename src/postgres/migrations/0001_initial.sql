@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS comment (id SERIAL,
+                                    reddit_id TEXT,
+                                    author TEXT,
+                                    subreddit TEXT,
+                                    body TEXT,
+                                    score INTEGER,
+                                    created_utc INTEGER,
+                                    retrieved_on INTEGER,
+                                    parent_id TEXT,
+                                    parent_is_post BOOLEAN,
+                                    flair INTEGER);
+
+CREATE TABLE IF NOT EXISTS posts (id SERIAL,
+                                pid TEXT,
+                                author TEXT,
+                                flair INTEGER,
+                                created_utc INTEGER,
+                                retrieved_on INTEGER,
+                                title TEXT,
+                                selftext TEXT,
+                                url TEXT,
+                                subreddit TEXT,
+                                score INTEGER);
+
+-- Databases set up by hand before migrations existed may predate these columns.
+ALTER TABLE comment ADD COLUMN IF NOT EXISTS flair INTEGER;
+ALTER TABLE posts ADD COLUMN IF NOT EXISTS selftext TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_comment_reddit_id ON comment (reddit_id);
+CREATE INDEX IF NOT EXISTS idx_parent_id ON comment (parent_id);
+CREATE INDEX IF NOT EXISTS idx_author ON comment (author);
+CREATE INDEX IF NOT EXISTS idx_subreddit ON comment (subreddit);
@@ -0,0 +1,5 @@
+-- Indexes dropped by a bulk import, recreated once it finishes. Keeping them here means an
+-- interrupted import can still restore them on the next run.
+CREATE TABLE IF NOT EXISTS deferred_index (name TEXT PRIMARY KEY,
+                                           tbl TEXT NOT NULL,
+                                           sql TEXT NOT NULL);
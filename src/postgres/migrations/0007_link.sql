@@ -0,0 +1,9 @@
+-- Links found in comment bodies, post self text and post urls while importing. type is 'comment'
+-- or 'post', reddit_id the comment's reddit_id or the post's pid.
+CREATE TABLE IF NOT EXISTS link (reddit_id TEXT NOT NULL,
+                                 type TEXT NOT NULL,
+                                 url TEXT NOT NULL,
+                                 domain TEXT NOT NULL);
+
+CREATE INDEX IF NOT EXISTS idx_link_domain ON link (domain);
+CREATE INDEX IF NOT EXISTS idx_link_reddit_id ON link (reddit_id);
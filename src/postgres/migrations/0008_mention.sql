@@ -0,0 +1,10 @@
+-- User and subreddit mentions found in comment bodies and post titles and self text while
+-- importing. type is 'comment' or 'post', reddit_id the comment's reddit_id or the post's pid.
+-- mention_type is 'user' or 'subreddit', target the lowercase name without u/ or r/.
+CREATE TABLE IF NOT EXISTS mention (reddit_id TEXT NOT NULL,
+                                    type TEXT NOT NULL,
+                                    mention_type TEXT NOT NULL,
+                                    target TEXT NOT NULL);
+
+CREATE INDEX IF NOT EXISTS idx_mention_target ON mention (mention_type, target);
+CREATE INDEX IF NOT EXISTS idx_mention_reddit_id ON mention (reddit_id);
@@ -0,0 +1,2 @@
+-- Parent ids were stored as "_abc" rather than "abc", the "_" of "t1_abc" left in by the import.
+UPDATE comment SET parent_id = substr(parent_id, 2) WHERE substr(parent_id, 1, 1) = '_';
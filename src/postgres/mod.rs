@@ -0,0 +1,406 @@
+mod backfill;
+mod bulk;
+mod copy;
+mod history;
+mod query;
+
+use anyhow::{bail, Result};
+use hashbrown::HashMap;
+use postgres::{Client, NoTls};
+
+use crate::{
+    backfill::{Backfill, UpdateCounts},
+    comment::Comment,
+    database::TextSearch,
+    flair::{flair_key, Flair},
+    flair_history::FlairHistory,
+    migrations::{AppliedMigration, Migration, Migrator},
+    post::Post,
+    value::Value,
+};
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "deferred_index",
+        sql: include_str!("migrations/0002_deferred_index.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "flair",
+        sql: include_str!("migrations/0003_flair.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "author_flair_history",
+        sql: include_str!("migrations/0004_author_flair_history.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "backfill",
+        sql: include_str!("migrations/0005_backfill.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "aggregate",
+        sql: include_str!("migrations/0006_aggregate.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "link",
+        sql: include_str!("migrations/0007_link.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "mention",
+        sql: include_str!("migrations/0008_mention.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "parent_id",
+        sql: include_str!("migrations/0009_parent_id.sql"),
+    },
+];
+
+pub struct Postgres {
+    connection: Client,
+    url: String,
+    // flair table ids by `flair_key`.
+    flair_ids: HashMap<String, i32>,
+    flair_history: FlairHistory<i32>,
+    // Rows waiting to be applied by `flush_backfill`, by reddit id.
+    backfill: Option<Backfill>,
+    staged: HashMap<String, Vec<Value>>,
+    update_counts: UpdateCounts,
+    // Rows waiting to be copied into `comment`, `posts`, `link` and `mention` by `flush_rows`.
+    comments: Vec<Vec<Value>>,
+    posts: Vec<Vec<Value>>,
+    links: Vec<Vec<Value>>,
+    mentions: Vec<Vec<Value>>,
+}
+
+impl Postgres {
+    pub fn new(url: &str) -> Result<Self> {
+        let connection = Client::connect(url, NoTls)?;
+        Ok(Postgres {
+            connection,
+            url: url.to_string(),
+            flair_ids: HashMap::new(),
+            flair_history: FlairHistory::new(),
+            backfill: None,
+            staged: HashMap::new(),
+            update_counts: UpdateCounts::default(),
+            comments: Vec::new(),
+            posts: Vec::new(),
+            links: Vec::new(),
+            mentions: Vec::new(),
+        })
+    }
+
+    /// Id of the flair table row for a flair, adding the row the first time it is seen.
+    fn flair_id(
+        &mut self,
+        subreddit: &str,
+        text: Option<&str>,
+        css_class: Option<&str>,
+        flair: &Flair,
+    ) -> Result<Option<i32>> {
+        let key = match flair_key(subreddit, text, css_class) {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        if let Some(id) = self.flair_ids.get(&key) {
+            return Ok(Some(*id));
+        }
+        let text = text.unwrap_or("");
+        let row = self.connection.query_one(
+            "INSERT INTO flair (subreddit, text, css_class, label, code) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (subreddit, text, css_class) DO UPDATE SET label = EXCLUDED.label, code = EXCLUDED.code \
+             RETURNING id",
+            &[
+                &subreddit,
+                &text,
+                &css_class.unwrap_or(""),
+                &flair.label(text),
+                &flair.code,
+            ],
+        )?;
+        let id: i32 = row.get(0);
+        self.flair_ids.insert(key, id);
+        Ok(Some(id))
+    }
+
+    pub fn insert_comment(&mut self, comment: &Comment) -> Result<usize> {
+        let flair_id = self.flair_id(
+            &comment.subreddit,
+            comment.author_flair_text.as_deref(),
+            comment.author_flair_css_class.as_deref(),
+            &comment.flair,
+        )?;
+        self.queue_extracted(&comment.id, "comment", &comment.links, &comment.mentions);
+        self.queue_comment(vec![
+            Value::Text(comment.id.clone()),
+            Value::Text(comment.author.clone()),
+            Value::Text(comment.subreddit.clone()),
+            Value::Text(comment.body.clone()),
+            Value::Integer(comment.score.into()),
+            Value::Integer(comment.created_utc.into()),
+            comment.retrieved_on.into(),
+            Value::Text(comment.parent_id.clone()),
+            Value::Integer(comment.parent_is_post as i64),
+            comment.flair.code.into(),
+            flair_id.into(),
+            comment.author_flair_text.clone().into(),
+        ])?;
+        if let Some(flair_id) = flair_id {
+            self.flair_history.record(
+                &comment.author,
+                &comment.subreddit,
+                flair_id,
+                comment.created_utc,
+            );
+            if self.flair_history.len() >= history::HISTORY_BATCH_SIZE {
+                self.flush_flair_history()?;
+            }
+        }
+
+        Ok(0)
+    }
+
+    pub fn update_counts(&self) -> UpdateCounts {
+        self.update_counts
+    }
+
+    /// Adds the generated `fts` tsvector column and its GIN index to `comment`.
+    ///
+    /// Run once the bulk load is done: computing the column for the whole table in one pass is
+    /// much cheaper than maintaining it row by row during the import. After that Postgres keeps it
+    /// in sync on insert and update by itself.
+    pub fn build_comment_fts(&mut self, language: &str) -> Result<()> {
+        let language = self.text_search_config(language)?;
+        self.build_fts(
+            "comment",
+            &language,
+            &format!(
+                "to_tsvector({0}, coalesce(body, '')) || \
+                 to_tsvector('simple', coalesce(author, '') || ' ' || coalesce(subreddit, ''))",
+                language
+            ),
+        )
+    }
+
+    /// Same as `build_comment_fts` for post titles and self text.
+    pub fn build_post_fts(&mut self, language: &str) -> Result<()> {
+        let language = self.text_search_config(language)?;
+        self.build_fts(
+            "posts",
+            &language,
+            &format!(
+                "setweight(to_tsvector({0}, coalesce(title, '')), 'A') || \
+                 setweight(to_tsvector({0}, coalesce(selftext, '')), 'B')",
+                language
+            ),
+        )
+    }
+
+    /// Resolves a text search configuration name (e.g. `english`) and returns it as a quoted
+    /// `regconfig` literal, failing if Postgres does not know it.
+    pub fn text_search_config(&mut self, language: &str) -> Result<String> {
+        let row = self
+            .connection
+            .query_one("SELECT $1::text::regconfig::text", &[&language])?;
+        let name: String = row.get(0);
+        Ok(format!("'{}'::regconfig", name.replace('\'', "''")))
+    }
+
+    /// Full text search through `table` with the `websearch_to_tsquery` query in parameter
+    /// `param`. Needs the `fts` column added by `build_comment_fts` or `build_post_fts`.
+    pub fn text_search(&mut self, table: &str, param: usize, language: &str) -> Result<TextSearch> {
+        let language = self.text_search_config(language)?;
+        let indexed = self
+            .connection
+            .query_opt(
+                "SELECT 1 FROM information_schema.columns \
+                 WHERE table_schema = current_schema() AND table_name = $1 AND column_name = 'fts'",
+                &[&table],
+            )?
+            .is_some();
+        if !indexed {
+            bail!(
+                "{} has no full text index, it is built at the end of an import without --skip-fts",
+                table
+            );
+        }
+        let text = match table {
+            "posts" => "coalesce(posts.title, '') || ' ' || coalesce(posts.selftext, '')",
+            _ => "coalesce(comment.body, '')",
+        };
+        Ok(TextSearch {
+            from: format!(
+                "{}, websearch_to_tsquery({}, ${}) AS text_query",
+                table, language, param
+            ),
+            condition: format!("{}.fts @@ text_query", table),
+            rank: format!("ts_rank_cd({}.fts, text_query)", table),
+            snippet: format!(
+                "ts_headline({}, {}, text_query, 'StartSel=**, StopSel=**, MaxWords=24, MinWords=8')",
+                language, text
+            ),
+        })
+    }
+
+    fn build_fts(&mut self, table: &str, language: &str, expression: &str) -> Result<()> {
+        let current: Option<String> = self
+            .connection
+            .query_opt(
+                "SELECT generation_expression::text FROM information_schema.columns \
+                 WHERE table_schema = current_schema() AND table_name = $1 AND column_name = 'fts'",
+                &[&table],
+            )?
+            .and_then(|row| row.get(0));
+        match current {
+            // The body/title configuration is the first one in the expression.
+            Some(current)
+                if current
+                    .split("::regconfig")
+                    .next()
+                    .unwrap_or("")
+                    .ends_with(language.trim_end_matches("::regconfig")) =>
+            {
+                println!("Full text index on {} is up to date", table);
+            }
+            _ => {
+                println!("Building full text index on {} ({})", table, language);
+                self.connection.batch_execute(&format!(
+                    "ALTER TABLE {0} DROP COLUMN IF EXISTS fts; \
+                     ALTER TABLE {0} ADD COLUMN fts tsvector GENERATED ALWAYS AS ({1}) STORED;",
+                    table, expression
+                ))?;
+            }
+        }
+        self.connection.batch_execute(&format!(
+            "CREATE INDEX IF NOT EXISTS idx_{0}_fts ON {0} USING GIN (fts)",
+            table
+        ))?;
+        Ok(())
+    }
+
+    pub fn update_comment_field_by_reddit_id(&mut self, comment: &Comment) -> Result<usize> {
+        if comment.id.is_empty() {
+            panic!()
+        }
+
+        let flair_id = self.flair_id(
+            &comment.subreddit,
+            comment.author_flair_text.as_deref(),
+            comment.author_flair_css_class.as_deref(),
+            &comment.flair,
+        )?;
+        if self.backfill.is_none() {
+            self.start_backfill(Backfill::flair())?;
+        }
+        self.stage_backfill(
+            comment.id.clone(),
+            vec![
+                comment.flair.code.into(),
+                flair_id.into(),
+                comment.author_flair_text.clone().into(),
+            ],
+        )?;
+        Ok(0)
+    }
+
+    pub fn insert_post(&mut self, post: &Post) -> Result<usize> {
+        let flair_code = post.flair.code;
+
+        let retrived_on_ = match &post.retrieved_on {
+            None => 0,
+            Some(s) => *s,
+        };
+
+        let subredit = match &post.subreddit {
+            None => "-1",
+            Some(s) => s,
+        };
+
+        let author = match &post.author {
+            None => "",
+            Some(s) => s,
+        };
+
+        if subredit != "-1" {
+            let flair_id = self.flair_id(
+                subredit,
+                post.author_flair_text.as_deref(),
+                post.author_flair_css_class.as_deref(),
+                &post.flair,
+            )?;
+            self.queue_extracted(&post.id, "post", &post.links, &post.mentions);
+            self.queue_post(vec![
+                Value::Text(post.id.clone()),
+                Value::Text(author.to_string()),
+                flair_code.into(),
+                Value::Integer(post.created_utc.into()),
+                Value::Integer(retrived_on_.into()),
+                Value::Text(post.title.clone()),
+                Value::Text(post.selftext.clone()),
+                Value::Text(post.url.clone()),
+                Value::Text(subredit.to_string()),
+                Value::Integer(post.score.into()),
+                flair_id.into(),
+                post.author_flair_text.clone().into(),
+            ])?;
+        }
+
+        Ok(0)
+    }
+}
+
+impl Migrator for Postgres {
+    fn migrations(&self) -> &'static [Migration] {
+        MIGRATIONS
+    }
+
+    fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>> {
+        let exists: bool = self
+            .connection
+            .query_one("SELECT to_regclass('schema_version') IS NOT NULL", &[])?
+            .get(0);
+        if !exists {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .connection
+            .query(
+                "SELECT version, to_char(applied_at, 'YYYY-MM-DD HH24:MI:SS') FROM schema_version ORDER BY version",
+                &[],
+            )?
+            .iter()
+            .map(|row| AppliedMigration {
+                version: row.get(0),
+                applied_at: row.get(1),
+            })
+            .collect())
+    }
+
+    fn apply(&mut self, migration: &Migration) -> Result<()> {
+        let mut transaction = self.connection.transaction()?;
+        transaction.batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER PRIMARY KEY,
+                                                        name TEXT NOT NULL,
+                                                        applied_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+        )?;
+        transaction.batch_execute(migration.sql)?;
+        transaction.execute(
+            "INSERT INTO schema_version (version, name) VALUES ($1, $2)",
+            &[&migration.version, &migration.name],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use postgres::types::ToSql;
+
+use super::Postgres;
+use crate::{
+    output::{Column, Kind},
+    value::Value,
+};
+
+// Rows fetched from the cursor at a time.
+const FETCH_SIZE: i32 = 10_000;
+
+impl Postgres {
+    /// Runs a query and hands each row to `f`. Rows are fetched from a cursor in batches, so a
+    /// large result never has to fit in memory.
+    pub fn query_each(
+        &mut self,
+        sql: &str,
+        params: &[Value],
+        f: &mut dyn FnMut(Vec<Value>) -> Result<()>,
+    ) -> Result<()> {
+        let params: Vec<&(dyn ToSql + Sync)> =
+            params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+        let mut transaction = self.connection.transaction()?;
+        let portal = transaction.bind(sql, &params)?;
+        loop {
+            let rows = transaction.query_portal(&portal, FETCH_SIZE)?;
+            if rows.is_empty() {
+                break;
+            }
+            for row in rows {
+                let values = (0..row.len())
+                    .map(|i| {
+                        row.try_get::<_, Value>(i).with_context(|| {
+                            format!("Can't read column {}", row.columns()[i].name())
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+                f(values)?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Runs a statement, returning the number of rows changed.
+    pub fn execute(&mut self, sql: &str, params: &[Value]) -> Result<usize> {
+        let params: Vec<&(dyn ToSql + Sync)> =
+            params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+        Ok(self.connection.execute(sql, &params)? as usize)
+    }
+
+    /// Runs each statement once for each of its parameter lists, in one transaction. Returns the
+    /// number of rows changed.
+    pub fn execute_batch(&mut self, statements: &[(&str, Vec<Vec<Value>>)]) -> Result<usize> {
+        let mut transaction = self.connection.transaction()?;
+        let mut changed = 0;
+        for (sql, rows) in statements {
+            let statement = transaction.prepare(sql)?;
+            for params in rows {
+                let params: Vec<&(dyn ToSql + Sync)> =
+                    params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+                changed += transaction.execute(&statement, &params)? as usize;
+            }
+        }
+        transaction.commit()?;
+        Ok(changed)
+    }
+
+    /// Replaces table `name` with an empty one with the given columns.
+    pub fn create_table(&mut self, name: &str, columns: &[Column]) -> Result<()> {
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let kind = match column.kind {
+                    Kind::Text => "TEXT",
+                    Kind::Integer => "BIGINT",
+                    Kind::Real => "DOUBLE PRECISION",
+                };
+                format!("{} {}", column.name, kind)
+            })
+            .collect();
+        self.connection.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {0}; CREATE TABLE {0} ({1});",
+            name,
+            columns.join(", ")
+        ))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+use std::time::Instant;
+
+use anyhow::Result;
+
+/// Runs one phase of a long operation, printing when it starts and how long it took.
+pub fn timed<T>(phase: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    println!("{}...", phase);
+    let start = Instant::now();
+    let result = f()?;
+    println!("{} done in {:.1?}", phase, start.elapsed());
+    Ok(result)
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use clap::ArgMatches;
+
+use super::{Record, Sink};
+use crate::{
+    comment::Comment,
+    output::{self, compress::Compression, Column, RowWriter, WriteOptions},
+    post::Post,
+};
+
+/// Writes comments or posts, whichever the operation imports, to one CSV or TSV file. The
+/// delimiter and compression come from the file extension, e.g. `comments.tsv.zst`.
+pub struct CsvSink {
+    writer: Option<Box<dyn RowWriter>>,
+    /// Positions in `Record::row` of the columns written.
+    selected: Vec<usize>,
+}
+
+impl CsvSink {
+    pub fn new(path: &Path, matches: &ArgMatches) -> Result<Self> {
+        let (_, name) = Compression::from_path(path);
+        match name.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") | Some("tsv") => {}
+            _ => bail!(
+                "--sink csv writes .csv or .tsv files, optionally compressed with .gz, .bz2, .xz or .zst"
+            ),
+        }
+        let columns = match matches.value_of("operation") {
+            Some("insert_post") => Post::columns(),
+            _ => Comment::columns(),
+        };
+        let selected = match matches.values_of("columns") {
+            Some(names) => names
+                .map(|name| {
+                    columns
+                        .iter()
+                        .position(|column| column.name == name)
+                        .ok_or_else(|| {
+                            let available: Vec<&str> =
+                                columns.iter().map(|column| column.name.as_str()).collect();
+                            anyhow!(
+                                "Unknown column {}, available columns are {}",
+                                name,
+                                available.join(", ")
+                            )
+                        })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => (0..columns.len()).collect(),
+        };
+        let options = WriteOptions {
+            header: !matches.is_present("no-header"),
+            ..WriteOptions::default()
+        };
+        let columns: Vec<Column> = selected.iter().map(|&i| columns[i].clone()).collect();
+        Ok(CsvSink {
+            writer: Some(output::create_with(path, columns, &options)?),
+            selected,
+        })
+    }
+
+    fn write(&mut self, record: &impl Record) -> Result<()> {
+        let row = record.row();
+        let row: Vec<_> = self.selected.iter().map(|&i| row[i].clone()).collect();
+        match &mut self.writer {
+            Some(writer) => writer.write(&row),
+            None => bail!("CSV output is already finished"),
+        }
+    }
+}
+
+impl Sink for CsvSink {
+    fn insert_comment(&mut self, comment: &Comment) -> Result<()> {
+        self.write(comment)
+    }
+
+    fn insert_post(&mut self, post: &Post) -> Result<()> {
+        self.write(post)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            let rows = writer.finish()?;
+            println!("{} rows written", rows);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{App, Arg};
+
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    fn matches(args: &[&str]) -> ArgMatches<'static> {
+        App::new("test")
+            .arg(
+                Arg::with_name("operation")
+                    .long("operation")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("columns")
+                    .long("columns")
+                    .takes_value(true)
+                    .use_delimiter(true),
+            )
+            .arg(Arg::with_name("no-header").long("no-header"))
+            .get_matches_from(std::iter::once(&"test").chain(args))
+    }
+
+    fn comment(id: &str, body: &str) -> Comment {
+        let mut comment = test_support::comment(id, "alice", "test", 1, 1);
+        comment.body = body.to_string();
+        comment
+    }
+
+    #[test]
+    fn selected_columns() {
+        let dir = TempDir::new("csv-sink");
+        let write = |name: &str, args: &[&str]| {
+            let path = dir.path().join(name);
+            let mut sink = CsvSink::new(&path, &matches(args)).unwrap();
+            sink.insert_comment(&comment("a", "plain")).unwrap();
+            sink.insert_comment(&comment("b", "with, \"quotes\"\nand lines"))
+                .unwrap();
+            sink.finish().unwrap();
+            std::fs::read_to_string(path).unwrap()
+        };
+        assert_eq!(
+            write("out.csv", &["--columns", "id,body"]),
+            "id,body\na,plain\nb,\"with, \"\"quotes\"\"\nand lines\"\n"
+        );
+        // No header, and the columns in the order asked for.
+        assert_eq!(
+            write("out.tsv", &["--columns", "score,id", "--no-header"]),
+            "1\ta\n1\tb\n"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_columns_and_extensions() {
+        let dir = TempDir::new("csv-sink-errors");
+        let err = CsvSink::new(
+            &dir.path().join("posts.csv"),
+            &matches(&["--operation", "insert_post", "--columns", "body"]),
+        )
+        .err()
+        .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("Unknown column body, available columns are id, author"));
+        assert!(CsvSink::new(&dir.path().join("out.json"), &matches(&[])).is_err());
+    }
+
+    #[test]
+    fn a_value_per_column() {
+        let comment = comment("a", "");
+        assert_eq!(comment.row().len(), Comment::columns().len());
+        let post = test_support::post("p", "alice", "test", 1, 1);
+        assert_eq!(post.row().len(), Post::columns().len());
+    }
+}
@@ -0,0 +1,75 @@
+//! Where imported records go. The default is a database; `--sink` picks a file format instead,
+//! written to the directory or file given in place of the database.
+
+mod csv;
+mod ndjson;
+mod parquet;
+mod records;
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+
+use crate::{comment::Comment, database::Database, post::Post, value::Value};
+
+pub use records::Record;
+
+pub trait Sink {
+    fn insert_comment(&mut self, comment: &Comment) -> Result<()>;
+
+    fn insert_post(&mut self, post: &Post) -> Result<()>;
+
+    fn update_flair(&mut self, _comment: &Comment) -> Result<()> {
+        bail!("update_flair needs a database")
+    }
+
+    fn stage_backfill(&mut self, _key: String, _values: Vec<Value>) -> Result<()> {
+        bail!("Backfills need a database")
+    }
+
+    /// Whether the sink takes the dump lines as read, through `write_line`, instead of parsed
+    /// comments and posts.
+    fn raw_lines(&self) -> bool {
+        false
+    }
+
+    fn write_line(&mut self, _line: &str) -> Result<()> {
+        bail!("This output only takes parsed comments and posts")
+    }
+
+    /// Writes out anything still buffered. Called once after the last record.
+    fn finish(&mut self) -> Result<()>;
+}
+
+impl Sink for Database {
+    fn insert_comment(&mut self, comment: &Comment) -> Result<()> {
+        Database::insert_comment(self, comment).map(|_| ())
+    }
+
+    fn insert_post(&mut self, post: &Post) -> Result<()> {
+        Database::insert_post(self, post).map(|_| ())
+    }
+
+    fn update_flair(&mut self, comment: &Comment) -> Result<()> {
+        self.update_comment_field_by_reddit_id(comment).map(|_| ())
+    }
+
+    fn stage_backfill(&mut self, key: String, values: Vec<Value>) -> Result<()> {
+        Database::stage_backfill(self, key, values)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.commit()
+    }
+}
+
+/// Opens the file sink named by `--sink`, writing to `target`.
+pub fn open(kind: &str, target: &Path, matches: &ArgMatches) -> Result<Box<dyn Sink>> {
+    match kind {
+        "csv" => Ok(Box::new(csv::CsvSink::new(target, matches)?)),
+        "ndjson" => Ok(Box::new(ndjson::NdjsonSink::new(target)?)),
+        "parquet" => Ok(Box::new(parquet::ParquetSink::new(target, matches)?)),
+        _ => bail!("Unknown sink {}", kind),
+    }
+}
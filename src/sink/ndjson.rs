@@ -0,0 +1,94 @@
+use std::{io::Write, path::Path};
+
+use anyhow::{bail, Result};
+
+use super::Sink;
+use crate::{
+    comment::Comment,
+    output::{
+        compress::{Compression, Encoder},
+        PartialFile,
+    },
+    post::Post,
+};
+
+/// Writes the dump lines that pass the filters to one file, exactly as they were read, so the
+/// output is a smaller dump in the same format. The compression comes from the file extension,
+/// e.g. `RC_dota2.zst`.
+pub struct NdjsonSink {
+    file: Option<(PartialFile, Encoder)>,
+    lines: usize,
+}
+
+impl NdjsonSink {
+    pub fn new(path: &Path) -> Result<Self> {
+        let (compression, _) = Compression::from_path(path);
+        let file = PartialFile::new(path);
+        let encoder = Encoder::new(file.create()?, compression)?;
+        Ok(NdjsonSink {
+            file: Some((file, encoder)),
+            lines: 0,
+        })
+    }
+}
+
+impl Sink for NdjsonSink {
+    fn insert_comment(&mut self, _comment: &Comment) -> Result<()> {
+        bail!("--sink ndjson writes dump lines, not comments")
+    }
+
+    fn insert_post(&mut self, _post: &Post) -> Result<()> {
+        bail!("--sink ndjson writes dump lines, not posts")
+    }
+
+    fn raw_lines(&self) -> bool {
+        true
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        match &mut self.file {
+            Some((_, encoder)) => {
+                encoder.write_all(line.as_bytes())?;
+                encoder.write_all(b"\n")?;
+            }
+            None => bail!("NDJSON output is already finished"),
+        }
+        self.lines += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some((file, encoder)) = self.file.take() {
+            encoder.finish()?;
+            file.commit()?;
+            println!("{} lines written", self.lines);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn lines_read_back_unchanged() {
+        let dir = TempDir::new("ndjson-sink");
+        let lines = [
+            r#"{"id":"a","body":"unknown fields are kept","extra":[1,2]}"#,
+            r#"{"id": "b",   "body": "so is spacing"}"#,
+        ];
+        for name in &["RC.gz", "RC.bz2", "RC.xz", "RC.zst"] {
+            let path = dir.path().join(name);
+            let mut sink = NdjsonSink::new(&path).unwrap();
+            for line in &lines {
+                sink.write_line(line).unwrap();
+            }
+            sink.finish().unwrap();
+            assert!(sink.write_line("late").is_err());
+            let read: Vec<String> = crate::iter_lines(&path).map(Result::unwrap).collect();
+            assert_eq!(read, lines, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use clap::ArgMatches;
+use hashbrown::HashMap;
+
+use super::{Record, Sink};
+use crate::{
+    comment::Comment,
+    output::{
+        parquet::{ParquetOptions, ParquetWriter},
+        Column, PartialFile, RowWriter,
+    },
+    post::Post,
+    timestamp,
+};
+
+// Partitions written to at the same time. Each one buffers up to a row group in memory, so when
+// there are more (say, partitioning a whole month by subreddit) the least recently used is
+// finished and a later record for it starts a new part file.
+const MAX_OPEN_PARTITIONS: usize = 32;
+
+/// Writes records to Parquet files under `<dir>/comments` and `<dir>/posts`, in Hive style
+/// `month=YYYY-MM` directories and optionally `subreddit=name` below those.
+pub struct ParquetSink {
+    comments: Partitions,
+    posts: Partitions,
+}
+
+impl ParquetSink {
+    pub fn new(dir: &Path, matches: &ArgMatches) -> Result<Self> {
+        let mut options = ParquetOptions::default();
+        if let Some(size) = matches.value_of("row-group-size") {
+            options.row_group_size = size.parse()?;
+        }
+        if let Some(compression) = matches.value_of("compression") {
+            options.compression = ParquetOptions::parse_compression(compression)?;
+        }
+        let by_subreddit = matches.is_present("partition-by-subreddit");
+        Ok(ParquetSink {
+            comments: Partitions::new(
+                dir.join("comments"),
+                Comment::columns(),
+                &options,
+                by_subreddit,
+            ),
+            posts: Partitions::new(dir.join("posts"), Post::columns(), &options, by_subreddit),
+        })
+    }
+}
+
+impl Sink for ParquetSink {
+    fn insert_comment(&mut self, comment: &Comment) -> Result<()> {
+        self.comments.write(comment)
+    }
+
+    fn insert_post(&mut self, post: &Post) -> Result<()> {
+        self.posts.write(post)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.comments.finish()?;
+        self.posts.finish()
+    }
+}
+
+struct Partitions {
+    dir: PathBuf,
+    columns: Vec<Column>,
+    options: ParquetOptions,
+    by_subreddit: bool,
+    open: HashMap<PathBuf, Partition>,
+    clock: u64,
+}
+
+struct Partition {
+    writer: Box<dyn RowWriter>,
+    last_used: u64,
+}
+
+impl Partitions {
+    fn new(
+        dir: PathBuf,
+        columns: Vec<Column>,
+        options: &ParquetOptions,
+        by_subreddit: bool,
+    ) -> Self {
+        Partitions {
+            dir,
+            columns,
+            options: options.clone(),
+            by_subreddit,
+            open: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn write(&mut self, record: &impl Record) -> Result<()> {
+        let mut dir = self
+            .dir
+            .join(format!("month={}", timestamp::month(record.created_utc())));
+        if self.by_subreddit {
+            dir.push(format!("subreddit={}", partition_value(record.subreddit())));
+        }
+        self.clock += 1;
+        if !self.open.contains_key(&dir) {
+            if self.open.len() >= MAX_OPEN_PARTITIONS {
+                self.close_least_recently_used()?;
+            }
+            let file = PartialFile::new(&next_part(&dir)?);
+            let writer = ParquetWriter::new(file, self.columns.clone(), &self.options)?;
+            self.open.insert(
+                dir.clone(),
+                Partition {
+                    writer: Box::new(writer),
+                    last_used: 0,
+                },
+            );
+        }
+        let partition = self.open.get_mut(&dir).unwrap();
+        partition.last_used = self.clock;
+        partition.writer.write(&record.row())
+    }
+
+    fn close_least_recently_used(&mut self) -> Result<()> {
+        let oldest = self
+            .open
+            .iter()
+            .min_by_key(|(_, partition)| partition.last_used)
+            .map(|(dir, _)| dir.clone());
+        if let Some(dir) = oldest {
+            let partition = self.open.remove(&dir).unwrap();
+            partition.writer.finish()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (dir, partition) in self.open.drain() {
+            let rows = partition.writer.finish()?;
+            println!("{}: {} rows", dir.display(), rows);
+        }
+        Ok(())
+    }
+}
+
+/// Partition directory names can't contain path separators; subreddit names never do, but the
+/// dump is not to be trusted.
+fn partition_value(value: Option<&str>) -> String {
+    match value {
+        Some(value) if !value.is_empty() => value
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect(),
+        _ => "__HIVE_DEFAULT_PARTITION__".to_string(),
+    }
+}
+
+/// The first `part-NNNNN.parquet` in `dir` that doesn't exist yet, so importing more dumps into
+/// the same directory adds files instead of replacing them.
+fn next_part(dir: &Path) -> Result<PathBuf> {
+    let mut next = 0;
+    if dir.exists() {
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let number = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("part-"))
+                .and_then(|name| name.split('.').next())
+                .and_then(|number| number.parse::<usize>().ok());
+            if let Some(number) = number {
+                next = next.max(number + 1);
+            }
+        }
+    }
+    Ok(dir.join(format!("part-{:05}.parquet", next)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    fn comment(id: &str, subreddit: &str, created_utc: i64) -> Comment {
+        test_support::comment(id, "alice", subreddit, 1, created_utc)
+    }
+
+    /// Files below `dir`, relative to `root`.
+    fn files_under(root: &Path, dir: &Path) -> Vec<String> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(files_under(root, &path));
+            } else {
+                let relative = path.strip_prefix(root).unwrap();
+                files.push(relative.to_str().unwrap().to_string());
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn hive_partitions() {
+        let dir = TempDir::new("parquet-sink");
+        // 2019-06-21 and 2019-07-01.
+        let (june, july) = (1561075200, 1561939200);
+        for _ in 0..2 {
+            let mut partitions = Partitions::new(
+                dir.path().to_path_buf(),
+                Comment::columns(),
+                &ParquetOptions::default(),
+                true,
+            );
+            partitions.write(&comment("a", "rust", june)).unwrap();
+            partitions.write(&comment("b", "r/../x", june)).unwrap();
+            partitions.write(&comment("c", "rust", july)).unwrap();
+            partitions.write(&comment("d", "", july)).unwrap();
+            partitions.finish().unwrap();
+        }
+        // A second import adds part files next to the first ones.
+        let mut files = files_under(dir.path(), dir.path());
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "month=2019-06/subreddit=r____x/part-00000.parquet",
+                "month=2019-06/subreddit=r____x/part-00001.parquet",
+                "month=2019-06/subreddit=rust/part-00000.parquet",
+                "month=2019-06/subreddit=rust/part-00001.parquet",
+                "month=2019-07/subreddit=__HIVE_DEFAULT_PARTITION__/part-00000.parquet",
+                "month=2019-07/subreddit=__HIVE_DEFAULT_PARTITION__/part-00001.parquet",
+                "month=2019-07/subreddit=rust/part-00000.parquet",
+                "month=2019-07/subreddit=rust/part-00001.parquet",
+            ]
+        );
+    }
+
+    #[test]
+    fn least_recently_used_partition_is_closed() {
+        let dir = TempDir::new("parquet-lru");
+        let mut partitions = Partitions::new(
+            dir.path().to_path_buf(),
+            Comment::columns(),
+            &ParquetOptions::default(),
+            true,
+        );
+        for i in 0..=MAX_OPEN_PARTITIONS {
+            partitions
+                .write(&comment("a", &format!("s{}", i), 0))
+                .unwrap();
+        }
+        assert_eq!(partitions.open.len(), MAX_OPEN_PARTITIONS);
+        // s0 was closed, so writing to it again starts a new part.
+        partitions.write(&comment("b", "s0", 0)).unwrap();
+        partitions.finish().unwrap();
+        let s0 = dir.path().join("month=1970-01/subreddit=s0");
+        assert!(s0.join("part-00000.parquet").is_file());
+        assert!(s0.join("part-00001.parquet").is_file());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    comment::Comment,
+    output::{Column, Kind},
+    post::Post,
+    value::Value,
+};
+
+/// A record that file sinks can write, one column per field.
+pub trait Record {
+    fn columns() -> Vec<Column>;
+
+    fn row(&self) -> Vec<Value>;
+
+    fn created_utc(&self) -> i64;
+
+    fn subreddit(&self) -> Option<&str>;
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+impl Record for Comment {
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("id", Kind::Text),
+            Column::new("author", Kind::Text),
+            Column::new("subreddit", Kind::Text),
+            Column::new("body", Kind::Text),
+            Column::new("score", Kind::Integer),
+            Column::new("created_utc", Kind::Integer),
+            Column::new("retrieved_on", Kind::Integer),
+            Column::new("link_id", Kind::Text),
+            Column::new("parent_id", Kind::Text),
+            Column::new("parent_is_post", Kind::Integer),
+            Column::new("author_flair_text", Kind::Text),
+            Column::new("author_flair_css_class", Kind::Text),
+            Column::new("flair", Kind::Integer),
+            Column::new("controversiality", Kind::Integer),
+            Column::new("distinguished", Kind::Text),
+            Column::new("stickied", Kind::Integer),
+        ]
+    }
+
+    fn row(&self) -> Vec<Value> {
+        vec![
+            text(&self.id),
+            text(&self.author),
+            text(&self.subreddit),
+            text(&self.body),
+            Value::Integer(self.score.into()),
+            Value::Integer(self.created_utc.into()),
+            self.retrieved_on.into(),
+            text(&self.link_id),
+            text(&self.parent_id),
+            Value::Integer(self.parent_is_post as i64),
+            self.author_flair_text.clone().into(),
+            self.author_flair_css_class.clone().into(),
+            self.flair.code.into(),
+            self.controversiality.into(),
+            self.distinguished.clone().into(),
+            Value::Integer(self.stickied as i64),
+        ]
+    }
+
+    fn created_utc(&self) -> i64 {
+        self.created_utc.into()
+    }
+
+    fn subreddit(&self) -> Option<&str> {
+        Some(&self.subreddit)
+    }
+}
+
+impl Record for Post {
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("id", Kind::Text),
+            Column::new("author", Kind::Text),
+            Column::new("subreddit", Kind::Text),
+            Column::new("title", Kind::Text),
+            Column::new("selftext", Kind::Text),
+            Column::new("url", Kind::Text),
+            Column::new("score", Kind::Integer),
+            Column::new("created_utc", Kind::Integer),
+            Column::new("retrieved_on", Kind::Integer),
+            Column::new("author_flair_text", Kind::Text),
+            Column::new("author_flair_css_class", Kind::Text),
+            Column::new("flair", Kind::Integer),
+        ]
+    }
+
+    fn row(&self) -> Vec<Value> {
+        vec![
+            text(&self.id),
+            self.author.clone().into(),
+            self.subreddit.clone().into(),
+            text(&self.title),
+            text(&self.selftext),
+            text(&self.url),
+            Value::Integer(self.score.into()),
+            Value::Integer(self.created_utc.into()),
+            self.retrieved_on.into(),
+            self.author_flair_text.clone().into(),
+            self.author_flair_css_class.clone().into(),
+            self.flair.code.into(),
+        ]
+    }
+
+    fn created_utc(&self) -> i64 {
+        self.created_utc.into()
+    }
+
+    fn subreddit(&self) -> Option<&str> {
+        self.subreddit.as_deref()
+    }
+}
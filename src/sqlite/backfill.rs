@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+
+use super::Sqlite;
+use crate::{
+    backfill::{Backfill, UpdateCounts},
+    value::Value,
+};
+
+impl Sqlite {
+    /// Sets up the staging table for `backfill`. Fails if the table lacks one of its columns.
+    pub fn start_backfill(&mut self, backfill: Backfill) -> Result<()> {
+        self.commit()?;
+        let select = format!(
+            "SELECT {}, {} FROM {}",
+            backfill.key,
+            backfill.columns.join(", "),
+            backfill.table
+        );
+        // Preparing the query first gets a readable error for a missing column.
+        self.connection
+            .prepare(&select)
+            .with_context(|| format!("Can't backfill {}", backfill.table))?;
+        self.connection.execute_batch(&format!(
+            "DROP TABLE IF EXISTS temp.backfill_stage; \
+             CREATE TEMP TABLE backfill_stage AS {} WHERE 0;",
+            select
+        ))?;
+        self.backfill = Some(backfill);
+        Ok(())
+    }
+
+    /// Queues new values for the row with reddit id `key`. A row staged twice keeps the last
+    /// values.
+    pub fn stage_backfill(&mut self, key: String, values: Vec<Value>) -> Result<()> {
+        self.begin()?;
+        self.staged.insert(key, values);
+        self.written()
+    }
+
+    /// Applies the staged rows with one `UPDATE ... FROM` rather than a lookup per row. Runs
+    /// inside the batch's transaction.
+    pub(super) fn flush_backfill(&mut self) -> Result<()> {
+        let backfill = match &self.backfill {
+            Some(backfill) if !self.staged.is_empty() => backfill,
+            _ => return Ok(()),
+        };
+        self.connection.execute("DELETE FROM backfill_stage", [])?;
+        {
+            let placeholders: Vec<String> = (1..=backfill.columns.len() + 1)
+                .map(|i| format!("?{}", i))
+                .collect();
+            let mut statement = self.connection.prepare_cached(&format!(
+                "INSERT INTO backfill_stage VALUES ({})",
+                placeholders.join(", ")
+            ))?;
+            for (key, values) in self.staged.drain() {
+                let mut params: Vec<&dyn rusqlite::ToSql> = vec![&key];
+                params.extend(values.iter().map(|value| value as &dyn rusqlite::ToSql));
+                statement.execute(params.as_slice())?;
+            }
+        }
+        self.connection.execute(
+            &format!(
+                "UPDATE {0} SET {1} FROM backfill_stage s WHERE {0}.{2} = s.{2}",
+                backfill.table,
+                backfill.assignments(),
+                backfill.key
+            ),
+            [],
+        )?;
+        let (staged, missing): (i64, i64) = self.connection.query_row(
+            &format!(
+                "SELECT count(*), count(*) FILTER (WHERE NOT EXISTS \
+                     (SELECT 1 FROM {0} WHERE {0}.{1} = s.{1})) \
+                 FROM backfill_stage s",
+                backfill.table, backfill.key
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let batch = UpdateCounts {
+            matched: (staged - missing) as usize,
+            missing: missing as usize,
+        };
+        batch.print("Batch");
+        self.update_counts.add(&batch);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{comment::Comment, migrations, test_support};
+
+    fn database() -> Sqlite {
+        let mut db = Sqlite::new(Path::new(":memory:")).unwrap();
+        migrations::migrate(&mut db).unwrap();
+        db
+    }
+
+    fn comment(id: &str, text: Option<&str>, code: Option<i32>) -> Comment {
+        let mut comment = test_support::comment(id, "alice", "test", 1, 1);
+        comment.author_flair_text = text.map(str::to_string);
+        comment.flair.code = code;
+        comment
+    }
+
+    fn flairs(db: &Sqlite) -> Vec<(String, Option<i32>, Option<String>)> {
+        db.connection
+            .prepare("SELECT reddit_id, flair, author_flair_text FROM comment ORDER BY reddit_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn flair_updates_replace_the_old_flair() {
+        let mut db = database();
+        db.insert_comment(&comment("a", Some("Old"), Some(1)))
+            .unwrap();
+        db.insert_comment(&comment("b", Some("Old"), Some(1)))
+            .unwrap();
+        db.commit().unwrap();
+
+        for update in &[
+            comment("a", Some("Older"), Some(2)),
+            // Staged twice, the last one wins.
+            comment("a", Some("New"), Some(3)),
+            // The flair columns are replaced even when the dump has none.
+            comment("b", None, None),
+            comment("missing", Some("New"), Some(3)),
+        ] {
+            db.update_comment_field_by_reddit_id(update).unwrap();
+        }
+        db.commit().unwrap();
+
+        assert_eq!(
+            flairs(&db),
+            [
+                ("a".to_string(), Some(3), Some("New".to_string())),
+                ("b".to_string(), None, None),
+            ]
+        );
+        let counts = db.update_counts();
+        assert_eq!((counts.matched, counts.missing), (2, 1));
+    }
+
+    #[test]
+    fn updates_existing_rows() {
+        let mut db = database();
+        for id in &["a", "b"] {
+            let mut comment = test_support::comment(id, "alice", "test", 1, 1);
+            comment.body = "old".to_string();
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+
+        let backfill = Backfill::from_fields("comment", "score,body".split(',')).unwrap();
+        db.start_backfill(backfill.clone()).unwrap();
+        for line in &[
+            serde_json::json!({"id": "a", "score": 10, "body": "new"}),
+            // No body in the dump, so the stored one is kept.
+            serde_json::json!({"id": "b", "score": 20}),
+            serde_json::json!({"id": "missing", "score": 30}),
+        ] {
+            let (id, values) = backfill.row(line).unwrap();
+            db.stage_backfill(id, values).unwrap();
+        }
+        db.commit().unwrap();
+        let counts = db.update_counts();
+        assert_eq!((counts.matched, counts.missing), (2, 1));
+
+        let rows: Vec<(String, i64, String)> = db
+            .connection
+            .prepare("SELECT reddit_id, score, body FROM comment ORDER BY reddit_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("a".to_string(), 10, "new".to_string()),
+                ("b".to_string(), 20, "old".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_columns_are_refused() {
+        let mut db = database();
+        let backfill = Backfill::from_fields("comment", ["gilded"].iter().copied()).unwrap();
+        let err = db.start_backfill(backfill).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Can't backfill comment: "));
+    }
+}
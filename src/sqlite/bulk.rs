@@ -0,0 +1,163 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+
+use super::{fts, Sqlite};
+use crate::{database::if_not_exists, progress::timed};
+
+impl Sqlite {
+    /// Drops the secondary indexes of `table` ahead of a bulk import, and with `fts` also the
+    /// trigger that adds new rows to its full text index. Returns how many were dropped.
+    pub fn defer_indexes(&mut self, table: &str, fts: bool) -> Result<usize> {
+        self.commit()?;
+        let transaction = self.connection.transaction()?;
+        let mut deferred: Vec<(String, String, Option<i64>)> = transaction
+            .prepare(
+                "SELECT name, sql FROM sqlite_master \
+                 WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL",
+            )?
+            .query_map([table], |row| Ok((row.get(0)?, row.get(1)?, None)))?
+            .collect::<rusqlite::Result<_>>()?;
+        if fts {
+            let trigger = format!("{}_ai", table);
+            let sql: Option<String> = transaction
+                .query_row(
+                    "SELECT sql FROM sqlite_master WHERE type = 'trigger' AND name = ?1",
+                    [&trigger],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(sql) = sql {
+                let after_id: i64 = transaction.query_row(
+                    &format!("SELECT coalesce(max(id), 0) FROM {}", table),
+                    [],
+                    |row| row.get(0),
+                )?;
+                deferred.push((trigger, sql, Some(after_id)));
+            }
+        }
+        for (name, sql, after_id) in &deferred {
+            transaction.execute(
+                "INSERT OR IGNORE INTO deferred_index (name, tbl, sql, after_id) VALUES (?1, ?2, ?3, ?4)",
+                params![name, table, sql, after_id],
+            )?;
+            let kind = if after_id.is_some() {
+                "TRIGGER"
+            } else {
+                "INDEX"
+            };
+            println!("Dropping {} {}", kind.to_lowercase(), name);
+            transaction.execute_batch(&format!("DROP {} {}", kind, name))?;
+        }
+        transaction.commit()?;
+        // Nothing is indexed yet if the import dies halfway, so trading durability for speed is fine.
+        self.connection.execute_batch("PRAGMA synchronous = OFF")?;
+        Ok(deferred.len())
+    }
+
+    /// Recreates everything dropped by `defer_indexes`, including by an earlier import that did
+    /// not finish. Rows added since a full text trigger was dropped are indexed first.
+    pub fn restore_indexes(&mut self) -> Result<()> {
+        self.commit()?;
+        let deferred: Vec<(String, String, String, Option<i64>)> = self
+            .connection
+            .prepare("SELECT name, tbl, sql, after_id FROM deferred_index ORDER BY name")?
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        for (name, table, sql, after_id) in deferred {
+            let phase = match after_id {
+                Some(_) => format!("Indexing new {} rows for full text search", table),
+                None => format!("Creating index {}", name),
+            };
+            timed(&phase, || {
+                let transaction = self.connection.transaction()?;
+                if let Some(after_id) = after_id {
+                    let fts = fts::for_content(&table)
+                        .expect("full text trigger on a table without an index");
+                    let columns = fts.columns.join(", ");
+                    transaction.execute(
+                        &format!(
+                            "INSERT INTO {0} (rowid, {1}) SELECT id, {1} FROM {2} WHERE id > ?1",
+                            fts.name, columns, table
+                        ),
+                        [after_id],
+                    )?;
+                }
+                transaction.execute_batch(&if_not_exists(&sql))?;
+                transaction.execute("DELETE FROM deferred_index WHERE name = ?1", [&name])?;
+                transaction.commit()?;
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn analyze(&mut self) -> Result<()> {
+        self.commit()?;
+        timed("Analyzing", || {
+            Ok(self.connection.execute_batch("ANALYZE")?)
+        })?;
+        timed("Vacuuming", || {
+            Ok(self.connection.execute_batch("VACUUM")?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{comment::Comment, migrations, test_support};
+
+    fn comment(id: &str) -> Comment {
+        let mut comment = test_support::comment(id, "alice", "test", 1, 1);
+        comment.body = "bulk loaded".to_string();
+        comment
+    }
+
+    fn schema(db: &Sqlite) -> Vec<String> {
+        db.connection
+            .prepare(
+                "SELECT name FROM sqlite_master WHERE tbl_name = 'comment' AND sql IS NOT NULL \
+                 AND type IN ('index', 'trigger') ORDER BY name",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn deferred_indexes_come_back() {
+        let mut db = Sqlite::new(Path::new(":memory:")).unwrap();
+        migrations::migrate(&mut db).unwrap();
+        db.insert_comment(&comment("a")).unwrap();
+        let before = schema(&db);
+        assert!(before.contains(&"comment_ai".to_string()));
+
+        let deferred = db.defer_indexes("comment", true).unwrap();
+        assert!(deferred > 1);
+        assert_eq!(schema(&db).len(), before.len() - deferred);
+        db.insert_comment(&comment("b")).unwrap();
+        db.insert_comment(&comment("c")).unwrap();
+
+        db.restore_indexes().unwrap();
+        assert_eq!(schema(&db), before);
+        // The rows added while the trigger was gone are in the full text index.
+        let report = fts::COMMENT_FTS.check(&db.connection).unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        let matches: i64 = db
+            .connection
+            .query_row(
+                "SELECT count(*) FROM comment_fts WHERE comment_fts MATCH 'bulk'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matches, 3);
+        db.restore_indexes().unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::database::TextSearch;
+
+/// An external content FTS5 table together with the table it indexes.
+pub struct FtsTable {
+    pub name: &'static str,
+    pub content: &'static str,
+    pub columns: &'static [&'static str],
+}
+
+pub const COMMENT_FTS: FtsTable = FtsTable {
+    name: "comment_fts",
+    content: "comment",
+    columns: &["author", "subreddit", "body"],
+};
+
+pub const POSTS_FTS: FtsTable = FtsTable {
+    name: "posts_fts",
+    content: "posts",
+    columns: &["title", "selftext"],
+};
+
+/// The FTS table indexing `content`, if there is one.
+pub fn for_content(content: &str) -> Option<&'static FtsTable> {
+    [&COMMENT_FTS, &POSTS_FTS]
+        .iter()
+        .copied()
+        .find(|fts| fts.content == content)
+}
+
+#[derive(Debug)]
+pub struct FtsReport {
+    /// Error returned by the FTS5 `integrity-check` command, if any.
+    pub integrity_error: Option<String>,
+    /// Rows that have no entry in the index.
+    pub missing: i64,
+    /// Index entries whose row no longer exists.
+    pub orphaned: i64,
+    /// Whether the comment_au trigger from before migration 0004 is still installed.
+    pub broken_trigger: bool,
+}
+
+impl FtsReport {
+    pub fn is_ok(&self) -> bool {
+        self.integrity_error.is_none()
+            && self.missing == 0
+            && self.orphaned == 0
+            && !self.broken_trigger
+    }
+}
+
+impl FtsTable {
+    /// Full text search through this table with the FTS5 query in parameter `param`. Snippets
+    /// come from the body of comments, or from whichever column matches best.
+    pub fn search(&self, param: usize) -> TextSearch {
+        let column = self
+            .columns
+            .iter()
+            .position(|column| *column == "body")
+            .map_or(-1, |i| i as i64);
+        TextSearch {
+            from: format!(
+                "{0} JOIN {1} ON {1}.id = {0}.rowid",
+                self.name, self.content
+            ),
+            condition: format!("{} MATCH ${}", self.name, param),
+            rank: format!("-bm25({})", self.name),
+            snippet: format!("snippet({}, {}, '**', '**', '...', 24)", self.name, column),
+        }
+    }
+
+    pub fn exists(&self, connection: &Connection) -> Result<bool> {
+        let count: i64 = connection.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [self.name],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn check(&self, connection: &Connection) -> Result<FtsReport> {
+        // A rank of 1 also compares the index against the content table, which is what catches
+        // rows indexed with the wrong text.
+        let integrity_error = connection
+            .execute(
+                &format!(
+                    "INSERT INTO {0} ({0}, rank) VALUES ('integrity-check', 1)",
+                    self.name
+                ),
+                [],
+            )
+            .err()
+            .map(|err| err.to_string());
+
+        // Every row of the content table is indexed, and the index keeps one `_docsize` row per
+        // document, so comparing ids finds the rows missing from it without scanning the terms.
+        let count_except = |from: &str, except: &str| -> Result<i64> {
+            Ok(connection.query_row(
+                &format!(
+                    "SELECT count(*) FROM (SELECT id FROM {} EXCEPT SELECT id FROM {})",
+                    from, except
+                ),
+                [],
+                |row| row.get(0),
+            )?)
+        };
+        let docsize = format!("{}_docsize", self.name);
+        let missing = count_except(self.content, &docsize)?;
+        let orphaned = count_except(&docsize, self.content)?;
+
+        let broken_trigger = self.name == COMMENT_FTS.name && {
+            let trigger: Option<String> = connection
+                .query_row(
+                    "SELECT sql FROM sqlite_master WHERE type = 'trigger' AND name = 'comment_au'",
+                    [],
+                    |row| row.get(0),
+                )
+                .ok();
+            trigger.is_some_and(|sql| sql.contains("new.subreddit, new.subreddit)"))
+        };
+
+        Ok(FtsReport {
+            integrity_error,
+            missing,
+            orphaned,
+            broken_trigger,
+        })
+    }
+
+    /// Re-creates the whole index from the content table.
+    pub fn rebuild(&self, connection: &Connection) -> Result<()> {
+        connection.execute(
+            &format!("INSERT INTO {0} ({0}) VALUES ('rebuild')", self.name),
+            [],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{migrations, sqlite::Sqlite, test_support};
+
+    fn database() -> Sqlite {
+        let mut db = Sqlite::new(Path::new(":memory:")).unwrap();
+        migrations::migrate(&mut db).unwrap();
+        for id in &["a", "b", "c"] {
+            let mut comment = test_support::comment(id, "alice", "test", 1, 1);
+            if *id != "c" {
+                comment.body = "hello world".to_string();
+            }
+            db.insert_comment(&comment).unwrap();
+        }
+        db.commit().unwrap();
+        db
+    }
+
+    #[test]
+    fn index_in_sync() {
+        let db = database();
+        let report = COMMENT_FTS.check(&db.connection).unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert!(POSTS_FTS.check(&db.connection).unwrap().is_ok());
+    }
+
+    #[test]
+    fn missing_and_orphaned_rows() {
+        let db = database();
+        db.connection
+            .execute_batch(
+                "INSERT INTO comment_fts (comment_fts, rowid, author, subreddit, body)
+                     SELECT 'delete', id, author, subreddit, body FROM comment WHERE reddit_id = 'a';
+                 INSERT INTO comment_fts (rowid, author, subreddit, body) VALUES (100, 'bob', 'test', 'gone');",
+            )
+            .unwrap();
+        let report = COMMENT_FTS.check(&db.connection).unwrap();
+        assert_eq!((report.missing, report.orphaned), (1, 1));
+        assert!(!report.is_ok());
+
+        COMMENT_FTS.rebuild(&db.connection).unwrap();
+        assert!(COMMENT_FTS.check(&db.connection).unwrap().is_ok());
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+use rusqlite::params;
+
+use super::Sqlite;
+use crate::flair_history::{FlairChange, Span, DELETED};
+
+impl Sqlite {
+    /// Merges the spans collected since the last batch into `author_flair_history`. Runs inside
+    /// the batch's transaction.
+    pub(super) fn flush_flair_history(&mut self) -> Result<()> {
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO author_flair_history (author, subreddit, flair_id, first_seen, last_seen, comment_count) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+             ON CONFLICT (author, subreddit, flair_id) DO UPDATE SET \
+             first_seen = min(first_seen, excluded.first_seen), \
+             last_seen = max(last_seen, excluded.last_seen), \
+             comment_count = comment_count + excluded.comment_count",
+        )?;
+        for ((author, subreddit, flair_id), span) in self.flair_history.drain() {
+            statement.execute(params![
+                author,
+                subreddit,
+                flair_id,
+                span.first_seen,
+                span.last_seen,
+                span.comment_count
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Recomputes `author_flair_history` from the comment table, for example after
+    /// `update_flair` has changed the flair of comments that were already counted.
+    pub fn rebuild_flair_history(&mut self) -> Result<usize> {
+        self.commit()?;
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM author_flair_history", [])?;
+        let rows = transaction.execute(
+            "INSERT INTO author_flair_history (author, subreddit, flair_id, first_seen, last_seen, comment_count) \
+             SELECT author, subreddit, flair_id, min(created_utc), max(created_utc), count(*) FROM comment \
+             WHERE flair_id IS NOT NULL AND author != ?1 \
+             GROUP BY author, subreddit, flair_id",
+            [DELETED],
+        )?;
+        transaction.commit()?;
+        Ok(rows)
+    }
+
+    /// Flair history of authors that used more than one flair in a subreddit.
+    pub fn flair_changes(&mut self, subreddit: Option<&str>) -> Result<Vec<FlairChange>> {
+        let mut statement = self.connection.prepare(
+            "SELECT h.author, h.subreddit, coalesce(f.label, f.text), h.first_seen, h.last_seen, h.comment_count \
+             FROM author_flair_history h JOIN flair f ON f.id = h.flair_id \
+             WHERE (h.author, h.subreddit) IN \
+                 (SELECT author, subreddit FROM author_flair_history \
+                  WHERE ?1 IS NULL OR subreddit = ?1 \
+                  GROUP BY author, subreddit HAVING count(*) > 1) \
+             ORDER BY h.author, h.subreddit, h.first_seen",
+        )?;
+        let changes = statement
+            .query_map([subreddit], |row| {
+                Ok(FlairChange {
+                    author: row.get(0)?,
+                    subreddit: row.get(1)?,
+                    flair: row.get(2)?,
+                    span: Span {
+                        first_seen: row.get(3)?,
+                        last_seen: row.get(4)?,
+                        comment_count: row.get(5)?,
+                    },
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(changes)
+    }
+}
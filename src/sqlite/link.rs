@@ -0,0 +1,23 @@
+use anyhow::Result;
+use rusqlite::params;
+
+use super::Sqlite;
+use crate::link::Link;
+
+impl Sqlite {
+    /// Adds the links found in a comment or post to `link`. Runs inside the batch's transaction.
+    pub(super) fn insert_links(
+        &mut self,
+        reddit_id: &str,
+        kind: &str,
+        links: &[Link],
+    ) -> Result<()> {
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO link (reddit_id, type, url, domain) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for link in links {
+            statement.execute(params![reddit_id, kind, link.url, link.domain])?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use rusqlite::params;
+
+use super::Sqlite;
+use crate::mention::Mention;
+
+impl Sqlite {
+    /// Adds the mentions found in a comment or post to `mention`. Runs inside the batch's
+    /// transaction.
+    pub(super) fn insert_mentions(
+        &mut self,
+        reddit_id: &str,
+        kind: &str,
+        mentions: &[Mention],
+    ) -> Result<()> {
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO mention (reddit_id, type, mention_type, target) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for mention in mentions {
+            statement.execute(params![reddit_id, kind, mention.kind, mention.target])?;
+        }
+        Ok(())
+    }
+}
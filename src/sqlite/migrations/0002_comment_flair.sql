@@ -0,0 +1 @@
+ALTER TABLE comment ADD COLUMN flair INTEGER;
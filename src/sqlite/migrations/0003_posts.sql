@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS posts (id INTEGER PRIMARY KEY,
+                                  pid TEXT,
+                                  author TEXT,
+                                  flair INTEGER,
+                                  created_utc INTEGER,
+                                  retrieved_on INTEGER,
+                                  title TEXT,
+                                  selftext TEXT,
+                                  url TEXT,
+                                  subreddit TEXT,
+                                  score INTEGER);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS posts_fts USING fts5(title, selftext, content = 'posts', content_rowid = 'id');
+
+CREATE TRIGGER IF NOT EXISTS posts_ai AFTER INSERT ON posts
+    BEGIN
+        INSERT INTO posts_fts (rowid, title, selftext)
+        VALUES (new.id, new.title, new.selftext);
+    END;
+
+CREATE TRIGGER IF NOT EXISTS posts_ad AFTER DELETE ON posts
+    BEGIN
+        INSERT INTO posts_fts (posts_fts, rowid, title, selftext)
+        VALUES ('delete', old.id, old.title, old.selftext);
+    END;
+
+CREATE TRIGGER IF NOT EXISTS posts_au AFTER UPDATE ON posts
+    BEGIN
+        INSERT INTO posts_fts (posts_fts, rowid, title, selftext)
+        VALUES ('delete', old.id, old.title, old.selftext);
+        INSERT INTO posts_fts (rowid, title, selftext)
+        VALUES (new.id, new.title, new.selftext);
+    END;
@@ -0,0 +1,13 @@
+-- Earlier versions of comment_au indexed the subreddit in place of the body of every updated
+-- comment. Replace the trigger and rebuild the index it corrupted.
+DROP TRIGGER IF EXISTS comment_au;
+
+CREATE TRIGGER comment_au AFTER UPDATE ON comment
+    BEGIN
+        INSERT INTO comment_fts (comment_fts, rowid, author, subreddit, body)
+        VALUES ('delete', old.id, old.author, old.subreddit, old.body);
+        INSERT INTO comment_fts (rowid, author, subreddit, body)
+        VALUES (new.id, new.author, new.subreddit, new.body);
+    END;
+
+INSERT INTO comment_fts (comment_fts) VALUES ('rebuild');
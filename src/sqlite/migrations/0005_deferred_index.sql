@@ -0,0 +1,7 @@
+-- Indexes and full text triggers dropped by a bulk import, recreated once it finishes. Keeping them
+-- here means an interrupted import can still restore them on the next run. after_id is set for
+-- full text triggers: rows with a larger id still have to be added to the index.
+CREATE TABLE IF NOT EXISTS deferred_index (name TEXT PRIMARY KEY,
+                                           tbl TEXT NOT NULL,
+                                           sql TEXT NOT NULL,
+                                           after_id INTEGER);
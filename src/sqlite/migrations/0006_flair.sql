@@ -0,0 +1,15 @@
+-- One row per distinct flair seen during imports. code and label come from the flair mapping,
+-- comment and posts point here through flair_id and keep the raw text next to it.
+CREATE TABLE IF NOT EXISTS flair (id INTEGER PRIMARY KEY,
+                                  subreddit TEXT NOT NULL,
+                                  text TEXT NOT NULL,
+                                  css_class TEXT NOT NULL DEFAULT '',
+                                  label TEXT,
+                                  code INTEGER,
+                                  UNIQUE (subreddit, text, css_class));
+
+ALTER TABLE comment ADD COLUMN flair_id INTEGER REFERENCES flair (id);
+ALTER TABLE comment ADD COLUMN author_flair_text TEXT;
+
+ALTER TABLE posts ADD COLUMN flair_id INTEGER REFERENCES flair (id);
+ALTER TABLE posts ADD COLUMN author_flair_text TEXT;
@@ -0,0 +1,9 @@
+-- Every flair an author has commented with per subreddit. Filled in during imports and rebuilt
+-- from comment after update_flair.
+CREATE TABLE IF NOT EXISTS author_flair_history (author TEXT NOT NULL,
+                                                 subreddit TEXT NOT NULL,
+                                                 flair_id INTEGER NOT NULL REFERENCES flair (id),
+                                                 first_seen INTEGER NOT NULL,
+                                                 last_seen INTEGER NOT NULL,
+                                                 comment_count INTEGER NOT NULL,
+                                                 PRIMARY KEY (author, subreddit, flair_id));
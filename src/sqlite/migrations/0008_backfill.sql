@@ -0,0 +1,8 @@
+-- Backfills find posts by pid.
+CREATE INDEX IF NOT EXISTS idx_posts_pid ON posts (pid);
+
+-- Not written by insert, fill them in with --operation backfill --fields edited,controversiality.
+-- edited is 0 for comments that were never edited, otherwise the time of the edit.
+ALTER TABLE comment ADD COLUMN edited INTEGER;
+ALTER TABLE comment ADD COLUMN controversiality INTEGER;
+ALTER TABLE posts ADD COLUMN edited INTEGER;
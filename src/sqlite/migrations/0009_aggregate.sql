@@ -0,0 +1,27 @@
+-- Rollups kept up to date by the aggregate subcommand and `--aggregate` imports. period is 'day'
+-- or 'month', start the unix time it begins at, in UTC.
+CREATE TABLE IF NOT EXISTS subreddit_activity (period TEXT NOT NULL,
+                                               subreddit TEXT NOT NULL,
+                                               start INTEGER NOT NULL,
+                                               comments INTEGER NOT NULL,
+                                               posts INTEGER NOT NULL,
+                                               authors INTEGER NOT NULL,
+                                               mean_comment_score REAL,
+                                               median_comment_score REAL,
+                                               mean_post_score REAL,
+                                               median_post_score REAL,
+                                               PRIMARY KEY (period, subreddit, start));
+
+-- The most active authors of each row of subreddit_activity, rank 1 first.
+CREATE TABLE IF NOT EXISTS subreddit_top_author (period TEXT NOT NULL,
+                                                 subreddit TEXT NOT NULL,
+                                                 start INTEGER NOT NULL,
+                                                 rank INTEGER NOT NULL,
+                                                 author TEXT NOT NULL,
+                                                 comments INTEGER NOT NULL,
+                                                 posts INTEGER NOT NULL,
+                                                 PRIMARY KEY (period, subreddit, start, rank));
+
+-- The highest comment and posts id already rolled up.
+CREATE TABLE IF NOT EXISTS aggregate_progress (source TEXT PRIMARY KEY,
+                                               last_id INTEGER NOT NULL);
@@ -0,0 +1,14 @@
+-- Parent ids were stored as "_abc" rather than "abc", the "_" of "t1_abc" left in by the import.
+-- Full text entries don't depend on parent_id, so comment_au is dropped for the update rather
+-- than reindexing every comment, then put back as it was.
+DROP TRIGGER IF EXISTS comment_au;
+
+UPDATE comment SET parent_id = substr(parent_id, 2) WHERE substr(parent_id, 1, 1) = '_';
+
+CREATE TRIGGER comment_au AFTER UPDATE ON comment
+    BEGIN
+        INSERT INTO comment_fts (comment_fts, rowid, author, subreddit, body)
+        VALUES ('delete', old.id, old.author, old.subreddit, old.body);
+        INSERT INTO comment_fts (rowid, author, subreddit, body)
+        VALUES (new.id, new.author, new.subreddit, new.body);
+    END;
@@ -0,0 +1,176 @@
+use anyhow::Result;
+
+use super::Sqlite;
+use crate::{
+    output::{Column, Kind},
+    value::Value,
+};
+
+/// Rewrites Postgres style `$1` placeholders to Sqlite's `?1`. A `$` inside a quoted string or
+/// identifier, or not followed by a digit, is left alone.
+fn placeholders(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut quote = None;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '$') if chars.peek().is_some_and(char::is_ascii_digit) => {
+                out.push('?');
+                continue;
+            }
+            _ => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl Sqlite {
+    /// Runs a query written with Postgres style `$1` placeholders and hands each row to `f`.
+    pub fn query_each(
+        &mut self,
+        sql: &str,
+        params: &[Value],
+        f: &mut dyn FnMut(Vec<Value>) -> Result<()>,
+    ) -> Result<()> {
+        self.commit()?;
+        let mut statement = self.connection.prepare(&placeholders(sql))?;
+        let count = statement.column_count();
+        let mut rows = statement.query(rusqlite::params_from_iter(params))?;
+        while let Some(row) = rows.next()? {
+            let values = (0..count)
+                .map(|i| row.get_ref(i).map(Value::from))
+                .collect::<rusqlite::Result<_>>()?;
+            f(values)?;
+        }
+        Ok(())
+    }
+
+    /// Runs a statement written with Postgres style `$1` placeholders. Returns the number of rows
+    /// changed.
+    pub fn execute(&mut self, sql: &str, params: &[Value]) -> Result<usize> {
+        self.commit()?;
+        Ok(self
+            .connection
+            .execute(&placeholders(sql), rusqlite::params_from_iter(params))?)
+    }
+
+    /// Runs each statement once for each of its parameter lists, in one transaction. Returns the
+    /// number of rows changed.
+    pub fn execute_batch(&mut self, statements: &[(&str, Vec<Vec<Value>>)]) -> Result<usize> {
+        self.commit()?;
+        let transaction = self.connection.transaction()?;
+        let mut changed = 0;
+        for (sql, rows) in statements {
+            let mut statement = transaction.prepare(&placeholders(sql))?;
+            for params in rows {
+                changed += statement.execute(rusqlite::params_from_iter(params))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(changed)
+    }
+
+    /// Replaces table `name` with an empty one with the given columns.
+    pub fn create_table(&mut self, name: &str, columns: &[Column]) -> Result<()> {
+        self.commit()?;
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let kind = match column.kind {
+                    Kind::Text => "TEXT",
+                    Kind::Integer => "INTEGER",
+                    Kind::Real => "REAL",
+                };
+                format!("{} {}", column.name, kind)
+            })
+            .collect();
+        self.connection.execute_batch(&format!(
+            "DROP TABLE IF EXISTS {0}; CREATE TABLE {0} ({1});",
+            name,
+            columns.join(", ")
+        ))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn rewrites_placeholders() {
+        assert_eq!(
+            placeholders("SELECT * FROM comment WHERE author = $1 AND score > $12"),
+            "SELECT * FROM comment WHERE author = ?1 AND score > ?12"
+        );
+        assert_eq!(
+            placeholders("SELECT '$1', \"a$2\", $3 || '$' FROM t WHERE x = $"),
+            "SELECT '$1', \"a$2\", ?3 || '$' FROM t WHERE x = $"
+        );
+        assert_eq!(
+            placeholders("SELECT 'it''s $1' WHERE a = $1"),
+            "SELECT 'it''s $1' WHERE a = ?1"
+        );
+    }
+
+    #[test]
+    fn numbered_parameters_can_repeat() {
+        let mut db = Sqlite::new(Path::new(":memory:")).unwrap();
+        db.create_table(
+            "t",
+            &[
+                Column::new("a", Kind::Text),
+                Column::new("b", Kind::Integer),
+            ],
+        )
+        .unwrap();
+        let changed = db
+            .execute_batch(&[(
+                "INSERT INTO t (a, b) VALUES ($2, $1)",
+                vec![
+                    vec![Value::Integer(1), Value::Text("x".to_string())],
+                    vec![Value::Integer(2), Value::Text("$1".to_string())],
+                ],
+            )])
+            .unwrap();
+        assert_eq!(changed, 2);
+        assert_eq!(
+            db.execute(
+                "UPDATE t SET b = b + $1 WHERE a != '$1' AND b < $1",
+                &[Value::Integer(5)]
+            )
+            .unwrap(),
+            1
+        );
+        let mut rows = Vec::new();
+        db.query_each(
+            "SELECT a, b, $1 FROM t ORDER BY a",
+            &[Value::Text("$".to_string())],
+            &mut |row| {
+                rows.push(row);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            [
+                vec![
+                    Value::Text("$1".to_string()),
+                    Value::Integer(2),
+                    Value::Text("$".to_string())
+                ],
+                vec![
+                    Value::Text("x".to_string()),
+                    Value::Integer(6),
+                    Value::Text("$".to_string())
+                ],
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Records and scratch directories shared by the unit tests.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{comment::Comment, post::Post};
+
+/// A top level comment on `t3_post` with an empty body.
+pub fn comment(id: &str, author: &str, subreddit: &str, score: i64, created_utc: i64) -> Comment {
+    Comment::from_json_str(
+        &serde_json::json!({
+            "id": id,
+            "parent_id": "t3_post",
+            "link_id": "t3_post",
+            "author": author,
+            "subreddit": subreddit,
+            "body": "",
+            "score": score,
+            "created_utc": created_utc,
+        })
+        .to_string(),
+    )
+}
+
+/// A comment replying to `parent_id`, which has its type prefix like `t1_abc` in the dump.
+pub fn reply(id: &str, author: &str, parent_id: &str, created_utc: i64) -> Comment {
+    Comment::from_json_str(
+        &serde_json::json!({
+            "id": id,
+            "parent_id": parent_id,
+            "link_id": "t3_post",
+            "author": author,
+            "subreddit": "test",
+            "body": "",
+            "score": 1,
+            "created_utc": created_utc,
+        })
+        .to_string(),
+    )
+}
+
+/// A post with an empty title, self text and url.
+pub fn post(id: &str, author: &str, subreddit: &str, score: i64, created_utc: i64) -> Post {
+    Post::from_json_str(
+        &serde_json::json!({
+            "id": id,
+            "author": author,
+            "subreddit": subreddit,
+            "title": "",
+            "url": "",
+            "score": score,
+            "created_utc": created_utc,
+            "retrieved_on": created_utc,
+        })
+        .to_string(),
+    )
+}
+
+/// A scratch directory for one test, removed with everything in it when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Tests run in parallel, so `name` must be unique to the test.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "pushshift-importer-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,161 @@
+//! Command line times, given either as unix seconds or as a UTC date like `2019-06-21` or
+//! `2019-06-21T08:30:00`.
+
+use anyhow::{bail, Context, Result};
+
+pub fn parse(text: &str) -> Result<i64> {
+    if let Ok(seconds) = text.parse::<i64>() {
+        return Ok(seconds);
+    }
+    let (date, time) = match text.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+    let parts = |s: &str, sep: char| -> Result<Vec<i64>> {
+        s.split(sep)
+            .map(|part| part.parse::<i64>().map_err(Into::into))
+            .collect()
+    };
+    let date = parts(date, '-').with_context(|| format!("Invalid time {:?}", text))?;
+    let (year, month, day) = match date.as_slice() {
+        [year, month] => (*year, *month, 1),
+        [year, month, day] => (*year, *month, *day),
+        _ => bail!("Invalid time {:?}, use unix seconds or YYYY-MM-DD", text),
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        bail!("Invalid date {:?}", text);
+    }
+    let mut seconds = days_from_civil(year, month, day) * 86_400;
+    if let Some(time) = time {
+        let time = parts(time.trim_end_matches('Z'), ':')
+            .with_context(|| format!("Invalid time {:?}", text))?;
+        let units = [3600, 60, 1];
+        if time.is_empty() || time.len() > 3 {
+            bail!("Invalid time {:?}", text);
+        }
+        seconds += time
+            .iter()
+            .zip(units.iter())
+            .map(|(t, u)| t * u)
+            .sum::<i64>();
+    }
+    Ok(seconds)
+}
+
+/// `YYYY-MM` of a unix time, in UTC.
+pub fn month(seconds: i64) -> String {
+    let (year, month, _) = civil_from_days(seconds.div_euclid(86_400));
+    format!("{:04}-{:02}", year, month)
+}
+
+/// `YYYY-MM-DD` of a unix time, in UTC.
+pub fn date(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Unix time of the start of the UTC month `seconds` falls in.
+pub fn month_start(seconds: i64) -> i64 {
+    let (year, month, _) = civil_from_days(seconds.div_euclid(86_400));
+    days_from_civil(year, month, 1) * 86_400
+}
+
+/// Unix time of the start of the UTC month after the one `seconds` falls in.
+pub fn next_month(seconds: i64) -> i64 {
+    let (year, month, _) = civil_from_days(seconds.div_euclid(86_400));
+    if month == 12 {
+        days_from_civil(year + 1, 1, 1) * 86_400
+    } else {
+        days_from_civil(year, month + 1, 1) * 86_400
+    }
+}
+
+/// Days since 1970-01-01, from Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_seconds_and_dates() {
+        assert_eq!(parse("1561075200").unwrap(), 1561075200);
+        assert_eq!(parse("-5").unwrap(), -5);
+        // A bare year is a number of seconds.
+        assert_eq!(parse("2019").unwrap(), 2019);
+        assert_eq!(parse("1970-01-01").unwrap(), 0);
+        assert_eq!(parse("2019-06-21").unwrap(), 1561075200);
+        assert_eq!(parse("2019-06").unwrap(), 1559347200);
+        assert_eq!(
+            parse("2019-06-21T08:30:00").unwrap(),
+            1561075200 + 8 * 3600 + 30 * 60
+        );
+        assert_eq!(
+            parse("2019-06-21 08:30Z").unwrap(),
+            1561075200 + 8 * 3600 + 30 * 60
+        );
+        assert_eq!(parse("2020-02-29").unwrap(), 1582934400);
+        assert_eq!(parse("1969-12-31").unwrap(), -86_400);
+    }
+
+    #[test]
+    fn rejects_bad_times() {
+        for text in &[
+            "",
+            "soon",
+            "2019-13-01",
+            "2019-06-32",
+            "2019-06-21T",
+            "2019-06-21T1:2:3:4",
+            "2019-06-21Tnoon",
+        ] {
+            assert!(parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn dates_and_months() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(1561075200 + 86_399), "2019-06-21");
+        assert_eq!(date(-1), "1969-12-31");
+        assert_eq!(date(parse("2000-02-29").unwrap()), "2000-02-29");
+        assert_eq!(month(1561075200), "2019-06");
+    }
+
+    #[test]
+    fn month_boundaries() {
+        let june = parse("2019-06-21T12:00:00").unwrap();
+        assert_eq!(month_start(june), parse("2019-06-01").unwrap());
+        assert_eq!(next_month(june), parse("2019-07-01").unwrap());
+        assert_eq!(
+            next_month(parse("2019-12-31").unwrap()),
+            parse("2020-01-01").unwrap()
+        );
+        assert_eq!(
+            month_start(parse("2019-07-01").unwrap()),
+            parse("2019-07-01").unwrap()
+        );
+    }
+}